@@ -12,7 +12,7 @@ fuzz_target!(|data: [String; 4]| {
     input: data.into(),
     print_ui: None,
   };
-  let mut io = FakeIO{fake_fs: HashMap::new(), fake_shell: HashMap::new()};
+  let mut io = FakeIO::new(HashMap::new(), HashMap::new());
   let macro_store = HashMap::new();
   let mut ed = add_ed::Ed::new(&mut io, &macro_store);
   loop {
@@ -1,6 +1,6 @@
 use add_ed::{Ed, Result};
 use add_ed::error::UIError;
-use add_ed::ui::{UI, UILock};
+use add_ed::ui::{UI, UILock, Confirmation};
 /// Error type for a [`ClassicUI`]
 #[derive(Debug)]
 enum ClassicUIError {
@@ -97,6 +97,30 @@ impl UI for ClassicUI {
     }
     Ok(())
   }
+  fn confirm_substitution(
+    &mut self,
+    text: &str,
+    span: (usize, usize),
+  ) -> Result<Confirmation> {
+    // Print the text with the match marked out by '^' on the line below
+    let line_start = text[.. span.0].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    print!("{}", text);
+    println!(
+      "{}{}",
+      " ".repeat(text[line_start .. span.0].chars().count()),
+      "^".repeat(text[span.0 .. span.1].chars().count().max(1)),
+    );
+    println!("Replace? (y/n/a/q)");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)
+      .map_err(|_| -> UIError { ClassicUIError::TerminalError.into() })?;
+    Ok(match input.trim() {
+      "y" => Confirmation::Yes,
+      "a" => Confirmation::All,
+      "q" => Confirmation::Quit,
+      _ => Confirmation::No,
+    })
+  }
   // Requires no additional code for locking and unlocking
   fn lock_ui(&mut self) -> UILock<'_> {
     UILock::new(self)
@@ -20,6 +20,18 @@ pub struct LinesIter<'a> {
   // enum internal, so we can have a low cost match during testing and no
   // overhead or generics complexity at runtime.
   inner: LinesIterInner<'a>,
+  // Set to leave out the newline ending the last line
+  unterminated: bool,
+  // The next line, read in advance to know which line is last if unterminated
+  peeked: Option<&'a str>,
+}
+impl<'a> LinesIter<'a> {
+  // Makes the iterator leave out the newline ending the last line, for files
+  // that didn't have one
+  pub(crate) fn unterminated(mut self) -> Self {
+    self.unterminated = true;
+    self
+  }
 }
 // Wrapped by struct, so we can hide the internal state
 enum LinesIterInner<'a> {
@@ -28,28 +40,37 @@ enum LinesIterInner<'a> {
   Test(Box<dyn Iterator<Item = &'a str>>),
 }
 
-impl<'a> Iterator for LinesIter<'a> {
-  type Item = &'a str;
-
-  fn next(&mut self) -> Option<Self::Item> {
-    match &mut self.inner {
+impl<'a> LinesIterInner<'a> {
+  fn next(&mut self) -> Option<&'a str> {
+    match self {
       LinesIterInner::Real(x) => x.next(),
       #[cfg(any(feature = "testing", fuzzing, test))]
       LinesIterInner::Test(x) => x.next(),
     }
   }
 }
+impl<'a> Iterator for LinesIter<'a> {
+  type Item = &'a str;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let line = self.peeked.take().or_else(|| self.inner.next())?;
+    if !self.unterminated { return Some(line); }
+    self.peeked = self.inner.next();
+    if self.peeked.is_some() { Some(line) }
+    else { Some(line.strip_suffix('\n').unwrap_or(line)) }
+  }
+}
 
 impl<'a> From<Inner<'a>> for LinesIter<'a> {
   fn from(i: Inner<'a>) -> Self {
-    Self{ inner: LinesIterInner::Real(i) }
+    Self{ inner: LinesIterInner::Real(i), unterminated: false, peeked: None }
   }
 }
 
 #[cfg(any(feature = "testing", fuzzing, test))]
 impl<'a, I: Iterator<Item=&'a str> + 'static> From<Box<I>> for LinesIter<'a> {
   fn from(i: Box<I>) -> Self {
-    Self{ inner: LinesIterInner::Test(i) }
+    Self{ inner: LinesIterInner::Test(i), unterminated: false, peeked: None }
   }
 }
 /// The iterator returned by [`Ed::get_tagged_selection`]
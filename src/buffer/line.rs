@@ -132,6 +132,12 @@ impl Line {
   pub fn set_tag(&self, new: char) {
     self.tag.set(new)
   }
+  // Identifies the line throughout history, since the tag is shared between
+  // all historical instances of the same line (see Snapshot impl below).
+  #[cfg(feature = "serde")]
+  pub(crate) fn id(&self) -> *const Cell<char> {
+    Rc::as_ptr(&self.tag)
+  }
 }
 // Our internal-only Clone implementation, to enable snapshotting without
 // misleading library users that they can Clone Lines.
@@ -0,0 +1,144 @@
+//! Support for editing multiple buffers in one [`Ed`]
+//!
+//! The active buffer is always the one in [`Ed`]'s `history`, `file`,
+//! `selection`, `missing_newline` and `line_ending` fields, so all commands
+//! work on it as usual. The other buffers are kept in
+//! [`Ed.buffers`](Ed::buffers) until switched to. The clipboard is shared
+//! between all buffers.
+//!
+//! Buffers are numbered from 1, in the order they were opened.
+
+use crate::{Ed, EdError, Result, History, Buffer, io::LineEnding};
+
+/// The state kept for each buffer that isn't active
+#[derive(Debug, Default)]
+pub struct OpenBuffer {
+  /// The buffer's undo history, see [`Ed.history`](Ed::history).
+  pub history: History<Buffer>,
+  /// The buffer's default file, see [`Ed.file`](Ed::file).
+  pub file: String,
+  /// The buffer's selection, see [`Ed.selection`](Ed::selection).
+  pub selection: (usize, usize),
+  /// If the buffer's file lacked a final newline, see
+  /// [`Ed.missing_newline`](Ed::missing_newline).
+  pub missing_newline: bool,
+  /// The line ending of the buffer's file, see
+  /// [`Ed.line_ending`](Ed::line_ending).
+  pub line_ending: LineEnding,
+}
+
+/// Holds the buffers not active in [`Ed`]
+///
+/// The slot for the active buffer is left empty, as its state lives in [`Ed`].
+/// Use the buffer methods on [`Ed`] to open, switch between and close buffers.
+#[derive(Debug)]
+pub struct BufferManager {
+  buffers: Vec<OpenBuffer>,
+  active: usize,
+}
+impl Default for BufferManager {
+  fn default() -> Self { Self::new() }
+}
+impl BufferManager {
+  /// Create a new BufferManager, with only the active buffer
+  pub fn new() -> Self {
+    Self{
+      buffers: vec![OpenBuffer::default()],
+      active: 0,
+    }
+  }
+  /// The number of open buffers, including the active one
+  pub fn count(&self) -> usize {
+    self.buffers.len()
+  }
+  /// The number of the active buffer
+  pub fn active(&self) -> usize {
+    self.active + 1
+  }
+  /// Get the state of the buffer with the given number
+  ///
+  /// Returns None for the active buffer (since its state is in [`Ed`]) and for
+  /// numbers without a buffer.
+  pub fn get(&self, nr: usize) -> Option<&OpenBuffer> {
+    if nr == self.active() { return None; }
+    self.buffers.get(nr.checked_sub(1)?)
+  }
+  // Convert a buffer number into an index, verifying that it exists
+  fn index(&self, nr: usize) -> Result<usize> {
+    match nr.checked_sub(1) {
+      Some(i) if i < self.buffers.len() => Ok(i),
+      _ => Err(EdError::BufferNotFound(nr.to_string())),
+    }
+  }
+}
+
+impl <'a> Ed<'a> {
+  // Swap the active buffer's state in Ed with the state in the given slot
+  fn swap_buffer(&mut self, i: usize) {
+    let slot = &mut self.buffers.buffers[i];
+    std::mem::swap(&mut self.history, &mut slot.history);
+    std::mem::swap(&mut self.file, &mut slot.file);
+    std::mem::swap(&mut self.selection, &mut slot.selection);
+    std::mem::swap(&mut self.missing_newline, &mut slot.missing_newline);
+    std::mem::swap(&mut self.line_ending, &mut slot.line_ending);
+  }
+
+  /// Open a new empty buffer and make it active
+  ///
+  /// The new buffer uses the same [`HistoryMode`](crate::HistoryMode) as the
+  /// currently active one. Returns the number of the new buffer.
+  pub fn open_buffer(&mut self) -> usize {
+    self.buffers.buffers.push(OpenBuffer{
+      history: History::with_mode(self.history.mode()),
+      file: String::new(),
+      selection: (1,0),
+      missing_newline: false,
+      line_ending: LineEnding::LF,
+    });
+    let nr = self.buffers.count();
+    // Safe to unwrap, as we just created it
+    self.switch_buffer(nr).unwrap();
+    nr
+  }
+
+  /// Make the buffer with the given number active
+  pub fn switch_buffer(&mut self, nr: usize) -> Result<()> {
+    let i = self.buffers.index(nr)?;
+    if i != self.buffers.active {
+      // Put away the active buffer, then take out the given one
+      self.swap_buffer(self.buffers.active);
+      self.swap_buffer(i);
+      self.buffers.active = i;
+    }
+    Ok(())
+  }
+
+  /// Close the buffer with the given number
+  ///
+  /// If closing the active buffer the preceding buffer becomes active (or the
+  /// following, if it was the first). Errors if the buffer has unsaved changes,
+  /// unless `force` is set, or if it is the only open buffer.
+  pub fn close_buffer(&mut self, nr: usize, force: bool) -> Result<()> {
+    let i = self.buffers.index(nr)?;
+    if self.buffers.count() == 1 { return Err(EdError::BufferLast); }
+    let saved = if i == self.buffers.active { self.history.saved() }
+      else { self.buffers.buffers[i].history.saved() }
+    ;
+    if !saved && !force { return Err(EdError::BufferUnsaved(nr)); }
+    if i == self.buffers.active {
+      // Switch away from it first, so the removed slot holds its state
+      self.switch_buffer(if i == 0 { 2 } else { i })?;
+    }
+    self.buffers.buffers.remove(i);
+    if i < self.buffers.active { self.buffers.active -= 1; }
+    Ok(())
+  }
+
+  /// Get the number of the first buffer with unsaved changes, if any
+  pub fn unsaved_buffer(&self) -> Option<usize> {
+    (1 ..= self.buffers.count()).find(|nr| match self.buffers.get(*nr) {
+      Some(buffer) => !buffer.history.saved(),
+      None => !self.history.saved(),
+    })
+  }
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+// Parse a buffer number, defaulting to the active buffer if none given
+fn parse_buffer_nr(state: &Ed<'_>, input: &str) -> Result<usize> {
+  let input = input.trim();
+  if input.is_empty() { return Ok(state.buffers.active()); }
+  input.parse::<usize>()
+    .map_err(|_| EdError::BufferNotFound(input.to_owned()))
+}
+
+pub fn manage_buffers(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  full_command: &str,
+  selection: Option<Sel<'_>>,
+  command: char,
+  tail: &str,
+) -> Result<()> {
+  if selection.is_some() { return Err(EdError::SelectionForbidden); }
+  match tail.chars().next() {
+    // Open a new buffer, reading in the given file if any
+    Some('+') => {
+      let path = &tail[1..];
+      let previous = state.buffers.active();
+      let nr = state.open_buffer();
+      if !path.trim().is_empty() {
+        if let Err(e) = read_from_file(state, ui, full_command, None, 'e', path) {
+          // Don't leave an empty buffer behind if the file couldn't be read
+          state.close_buffer(nr, true)?;
+          state.switch_buffer(previous)?;
+          return Err(e);
+        }
+      }
+    },
+    // Close the given buffer, 'B' ignores unsaved changes
+    Some('-') => {
+      let nr = parse_buffer_nr(state, &tail[1..])?;
+      state.close_buffer(nr, command == 'B')?;
+    },
+    // List the open buffers
+    None => {
+      let active = state.buffers.active();
+      let listing: Vec<String> = (1 ..= state.buffers.count()).map(|nr| {
+        let (file, saved) = match state.buffers.get(nr) {
+          Some(b) => (&b.file, b.history.saved()),
+          None => (&state.file, state.history.saved()),
+        };
+        format!(
+          "{} {}: {} {}",
+          if nr == active { '>' } else { ' ' },
+          nr,
+          file,
+          if saved { "" } else { "(unsaved)" },
+        )
+      }).collect();
+      ui.print_message(&listing.join("\n"))?;
+    },
+    // Switch to the given buffer
+    Some(_) => {
+      let nr = parse_buffer_nr(state, tail)?;
+      state.switch_buffer(nr)?;
+    },
+  }
+  Ok(())
+}
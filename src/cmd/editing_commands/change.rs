@@ -8,13 +8,14 @@ fn inner_change(
 ) -> Result<()> {
   let buffer = state.history.current_mut(full_command.into());
   let mut tail = buffer.split_off(selection.1);
-  state.clipboard = buffer.split_off(selection.0 - 1)[..].into();
+  let before = buffer.split_off(selection.0 - 1);
   // Note that drain gives full Strings and Line::new will use them as-is,
   // without re-allocating them (but risking leaving them over allocated).
   for line in input.drain(..) {
     buffer.push(Line::new(line).map_err(InternalError::InvalidLineText)?);
   }
   buffer.append(&mut tail);
+  state.set_clipboard(before[..].into());
   Ok(())
 }
 pub fn change(
@@ -3,9 +3,14 @@ use super::*;
 fn inner_copy(
   state: &mut Ed<'_>,
   selection: (usize, usize),
+  register: Option<char>,
 ) -> Result<()> {
   let buffer = state.history.current();
-  state.clipboard = buffer[selection.0 - 1 .. selection.1].into();
+  let data = buffer[selection.0 - 1 .. selection.1].into();
+  match register {
+    Some(r) => { state.registers.insert(r, data); },
+    None => state.set_clipboard(data),
+  }
   Ok(())
 }
 pub fn copy(
@@ -16,11 +21,12 @@ pub fn copy(
 ) -> Result<()> {
   let sel = interpret_selection(&state, selection, state.selection)?;
   state.history.current().verify_selection(sel)?;
+  let (register, tail) = parse_register(tail)?;
   let mut flags = parse_flags(tail, "pnl")?;
   pflags.p = flags.remove(&'p').unwrap();
   pflags.n = flags.remove(&'n').unwrap();
   pflags.l = flags.remove(&'l').unwrap();
-  inner_copy(state, sel)?;
+  inner_copy(state, sel, register)?;
   state.selection = sel;
   Ok(())
 }
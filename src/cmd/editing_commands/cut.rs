@@ -10,6 +10,7 @@ pub fn cut(
   let sel = interpret_selection(&state, selection, state.selection)?;
   let buffer = state.history.current();
   buffer.verify_selection(sel)?;
+  let (register, tail) = parse_register(tail)?;
   // Since selection after execution can be 0 it isn't allowed to auto print after
   // Get the flags
   let mut flags = parse_flags(tail, "pnl")?;
@@ -28,7 +29,6 @@ pub fn cut(
   let mut tail = buffer.split_off(sel.1);
   let data = buffer.split_off(sel.0 - 1);
   buffer.append(&mut tail);
-  state.clipboard = data[..].into();
   // Try to figure out a selection after the deletion
   state.selection = {
     // For deletion behaviour try to select:
@@ -47,5 +47,9 @@ pub fn cut(
       sel.0.min(buffer.len())
     )
   };
+  match register {
+    Some(r) => { state.registers.insert(r, data[..].into()); },
+    None => state.set_clipboard(data[..].into()),
+  }
   Ok(())
 }
@@ -65,7 +65,7 @@ fn inner_inline_input(
     },
   }
   buffer.append(&mut tail);
-  state.clipboard = (&*indexed_line).into();
+  state.set_clipboard((&*indexed_line).into());
   Ok(())
 }
 pub fn input(
@@ -21,7 +21,7 @@ fn inner_join(
   buffer.push(Line::new(text).map_err(InternalError::InvalidLineText)?);
   // Add back tail data and save old data into clipboard
   buffer.append(&mut tail);
-  state.clipboard = data[..].into();
+  state.set_clipboard(data[..].into());
   Ok(())
 }
 pub fn join(
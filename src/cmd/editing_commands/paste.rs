@@ -4,13 +4,25 @@ fn inner_paste(
   state: &mut Ed<'_>,
   full_command: &str,
   index: usize,
+  register: Option<char>,
+  ring_index: usize,
 ) -> Result<usize> {
   state.history.current().verify_index(index)?;
+  let mut data: Vec<Line> = match register {
+    Some(r) => state.registers.get(&r).ok_or(EdError::RegisterEmpty(r))?.into(),
+    None => state.clipboard_entry(ring_index)
+      .ok_or(EdError::ClipboardIndexTooBig{
+        index: ring_index,
+        ring_len: state.clipboard_history.len() + 1,
+      })?
+      .into(),
+  };
+  let length = data.len();
   let buffer = state.history.current_mut(full_command.into());
   let mut tail = buffer.split_off(index);
-  buffer.append(&mut (&state.clipboard).into() );
+  buffer.append(&mut data);
   buffer.append(&mut tail);
-  Ok(state.clipboard.len())
+  Ok(length)
 }
 pub fn paste(
   state: &mut Ed<'_>,
@@ -21,6 +33,17 @@ pub fn paste(
   tail: &str,
 ) -> Result<()> {
   let sel = interpret_selection(&state, selection, state.selection)?;
+  let (register, tail) = parse_register(tail)?;
+  // If no register is given an index into the clipboard ring may be
+  let (ring_index, tail) = if register.is_some() { ("", tail) } else {
+    tail.split_at(
+      tail.find(|c: char| !c.is_ascii_digit()).unwrap_or(tail.len())
+    )
+  };
+  let ring_index = if ring_index.is_empty() { 0 } else {
+    ring_index.parse::<usize>()
+      .map_err(|_| EdError::ClipboardIndexNotInt(ring_index.to_owned()))?
+  };
   let mut flags = parse_flags(tail, "pnl")?;
   pflags.p = flags.remove(&'p').unwrap();
   pflags.n = flags.remove(&'n').unwrap();
@@ -30,7 +53,7 @@ pub fn paste(
     if command == 'X' { sel.0.saturating_sub(1) }
     else { sel.1 }
   ;
-  let length = inner_paste(state, full_command, index)?;
+  let length = inner_paste(state, full_command, index, register, ring_index)?;
   state.selection =
     if length != 0 {
       (index + 1, index + length)
@@ -39,3 +62,26 @@ pub fn paste(
   ;
   Ok(())
 }
+pub fn list_clipboards(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  selection: Option<Sel<'_>>,
+  tail: &str,
+) -> Result<()> {
+  if selection.is_some() { return Err(EdError::SelectionForbidden); }
+  parse_flags(tail, "")?;
+  // Show the first line of each entry and how many lines it holds
+  let listing: Vec<String> = std::iter::once(&state.clipboard)
+    .chain(state.clipboard_history.iter())
+    .enumerate()
+    .map(|(i, clipboard)| format!(
+      "{}: {} ({} line(s))",
+      i,
+      clipboard.first().map(|l| l.text.trim_end_matches('\n')).unwrap_or(""),
+      clipboard.len(),
+    ))
+    .collect()
+  ;
+  ui.print_message(&listing.join("\n"))?;
+  Ok(())
+}
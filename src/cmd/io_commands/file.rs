@@ -87,9 +87,11 @@ pub fn read_from_file(
         if changed {
           ui.print_message( &substituted )?;
         }
+        let context = shell_context(state, state.selection);
         let data = state.io.run_read_command(
           &mut ui.lock_ui(),
           substituted.clone(),
+          &context,
         )?;
         state.prev_shell_command = substituted;
         data
@@ -98,6 +100,11 @@ pub fn read_from_file(
         state.io.read_file(file, command == 'E')?
       },
     };
+    // The buffer requires newlines, so one is added if missing
+    let unterminated = !unformated_data.is_empty() &&
+      !unformated_data.ends_with('\n')
+    ;
+    if unterminated { ui.print_message(NEWLINE_APPENDED)?; }
     let data = unformated_data.lines();
     let datalen = match index {
       Some(i) => insert(state.history.current_mut(full_command.into()), data, i),
@@ -110,6 +117,11 @@ pub fn read_from_file(
       // Considering saved after command is odd, and commands cannot be saved
       // into state.file, only aftereffect is state.prev_shell_command
       Path::Command(_cmd) => {
+        // The buffer's contents no longer come from a file
+        if command != 'r' {
+          state.missing_newline = false;
+          state.line_ending = LineEnding::LF;
+        }
         ui.print_message(&format!(
           "Read {} bytes from command `{}`",
           unformated_data.len(),
@@ -127,7 +139,24 @@ pub fn read_from_file(
         // it normally won't.
         if state.history.current().len() == datalen && command != 'r' {
           state.file = file.to_owned();
+          // Remember these, so 'w' can write the file back the same way
+          state.missing_newline = unterminated;
+          state.line_ending = LineEnding::detect(&unformated_data)
+            .unwrap_or(LineEnding::LF);
           state.history.set_saved();
+          // If there is history persisted for the file, try to resume it
+          #[cfg(feature = "serde")]
+          if state.persist_history {
+            match state.load_history() {
+              Ok(true) => ui.print_message("Loaded persisted undo history.")?,
+              Ok(false) => (),
+              // An invalid history file shouldn't prevent opening the file
+              Err(e) => ui.print_message(&format!(
+                "Ignored persisted undo history. {}",
+                e,
+              ))?,
+            }
+          }
         }
       },
     }
@@ -142,6 +171,8 @@ pub fn write_to_file(
   command: char,
   in_path: &str,
 ) -> Result<bool> {
+  // Only a directly repeated write may force overwriting a changed file
+  let warned = state.changed_file_warning.take();
   // Since 'w' and 'W' should default to the whole buffer rather than previous selection
   // they get some custom code here
   let sel = match selection {
@@ -178,9 +209,23 @@ pub fn write_to_file(
   match path {
     Path::File(file) => {
       let append = command == 'W';
+      // Don't overwrite changes made by others, unless the previous command
+      // was refused for that reason (meaning it is repeated to force it)
+      if !append && state.io.file_changed(file)? && warned.as_deref() != Some(file)
+      {
+        state.changed_file_warning = Some(file.to_owned());
+        return Err(EdError::FileChanged(file.to_owned()));
+      }
+      // If the file lacked a final newline, save it the same way
+      let data = if sel.is_none() && !append && state.missing_newline {
+        data.unterminated()
+      } else {
+        data
+      };
       let written = state.io.write_file(
         file,
         append,
+        state.line_ending,
         data,
       )?;
       ui.print_message(&format!(
@@ -195,6 +240,8 @@ pub fn write_to_file(
       if sel.is_none() && command != 'W' {
         state.file = file.to_string();
         state.history.set_saved();
+        #[cfg(feature = "serde")]
+        if state.persist_history { state.save_history()?; }
       }
     },
     Path::Command(cmd) => {
@@ -207,11 +254,16 @@ pub fn write_to_file(
         &state.file,
         &state.prev_shell_command,
       )?;
+      let context = shell_context(
+        state,
+        sel.unwrap_or((1, state.history.current().len())),
+      );
       state.prev_shell_command = substituted.clone();
       if changed {ui.print_message( &substituted )?;}
       let written = state.io.run_write_command(
         &mut ui.lock_ui(),
         substituted,
+        &context,
         data,
       )?;
       ui.print_message(&format!(
@@ -4,3 +4,21 @@ mod file;
 pub use file::*;
 mod shell;
 pub use shell::*;
+
+use crate::io::{ShellContext, LineEnding};
+
+// Gathers the editor state to give to a shell command
+//
+// Must be called before updating state.prev_shell_command to the command.
+fn shell_context(
+  state: &Ed<'_>,
+  selection: (usize, usize),
+) -> ShellContext {
+  ShellContext{
+    file: state.file.clone(),
+    selection,
+    buffer_len: state.history.current().len(),
+    prev_shell_command: state.prev_shell_command.clone(),
+    scripted: state.scripted,
+  }
+}
@@ -10,11 +10,12 @@ fn replace_selection(
   // Selection already verified by get_selection call before calling this fn
   let buffer = state.history.current_mut(full_command.into());
   let mut tail = buffer.split_off(selection.1);
-  state.clipboard = buffer.split_off(selection.0 - 1)[..].into();
+  let before = buffer.split_off(selection.0 - 1);
   for line in input.drain(..) {
     buffer.push(Line::new(line).map_err(InternalError::InvalidLineText)?);
   }
   buffer.append(&mut tail);
+  state.set_clipboard(before[..].into());
   Ok(())
 }
 pub fn run_command(
@@ -41,6 +42,7 @@ pub fn run_command(
     &state.file,
     &state.prev_shell_command,
   )?;
+  let context = shell_context(state, sel.unwrap_or(state.selection));
   state.prev_shell_command = substituted.clone();
   if changed {ui.print_message( &substituted )?;}
   // Depending on selection or not we use run_transform_command or run_command
@@ -50,6 +52,7 @@ pub fn run_command(
       let res = state.io.run_command(
         &mut ui.lock_ui(),
         substituted,
+        &context,
       );
       // Signify end of command output before reacting to potential error
       ui.print_message(&ch.to_string())?;
@@ -62,6 +65,7 @@ pub fn run_command(
       let transformed = state.io.run_transform_command(
         &mut ui.lock_ui(),
         substituted,
+        &context,
         data,
       )?;
       let lines: Vec<&str> = transformed.split_inclusive('\n').collect();
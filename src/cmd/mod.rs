@@ -1,12 +1,22 @@
 use crate::{Ed, Substitution, Line, Clipboard, Buffer};
-use crate::ui::{UI, ScriptedUI};
+use crate::ui::{UI, ScriptedUI, Confirmation};
 use crate::error::*;
 use crate::messages::*;
+use crate::regex_engine::RegexEngine;
 
 
 // Parsing helpers
 mod parsing;
 use parsing::*;
+// The helpers exposed to command extenders
+pub use parsing::{
+  Sel,
+  Ind,
+  interpret_selection,
+  parse_flags,
+  parse_expressions,
+  RegexFlags,
+};
 
 // Command logic in separate loosely grouped files, to manage file size
 //
@@ -18,10 +28,14 @@ mod editing_commands;
 use editing_commands::*;
 mod regex_commands;
 use regex_commands::*;
+pub(crate) use regex_commands::{find_matches, preview_substitution};
 
 mod undo;
 use undo::*;
 
+mod buffer_commands;
+use buffer_commands::*;
+
 // Helps to hand in globally relevant flags as one &mut struct to the command
 // implementations
 // (pub because rusts pub fn is a bit clunky and complains otherwise)
@@ -55,17 +69,25 @@ pub(crate) fn run(
   if recursion_depth > state.recursion_limit {
     return Err(EdError::InfiniteRecursion);
   }
+  // Only reset on top level commands, so it reports on nested ones as well
+  if recursion_depth == 0 { state.search_wrapped.set(false); }
 
   // Declare flags for printing after the command has been executed.
   let mut pflags = PrintingFlags::default();
 
   // Parse out the command index and the selection
   let (cmd_i, selection) = parse_selection(command)?;
+  remember_patterns(state, &selection)?;
 
   // Use the cmd_i to get a clean selection  
   // Match the command and act upon it
    // (Trim end to get None instead of '\n' or ' ' if there is no command)
-   let ret = match command[cmd_i..].trim_end().chars().next() {
+   let cmd_char = command[cmd_i..].trim_end().chars().next();
+   // Any other command means a refused write is no longer being repeated
+   if recursion_depth == 0 && cmd_char != Some('w') {
+     state.changed_file_warning = None;
+   }
+   let ret = match cmd_char {
     // No command is valid. It updates selection and prints
     None => {
       if selection.is_some() {
@@ -98,12 +120,19 @@ pub(crate) fn run(
         'q' | 'Q' => {
           if selection.is_some() { return Err(EdError::SelectionForbidden); }
           parse_flags(clean, "")?;
-          if state.history.saved() || ch == 'Q' {
+          if ch == 'Q' {
             Ok(true)
           }
-          else {
+          else if !state.history.saved() {
             Err(EdError::UnsavedChanges)
           }
+          // Other open buffers must also be saved
+          else if let Some(nr) = state.unsaved_buffer() {
+            Err(EdError::BufferUnsaved(nr))
+          }
+          else {
+            Ok(true)
+          }
         }
         // Help commands
         'h' => {
@@ -172,6 +201,10 @@ pub(crate) fn run(
         'w' | 'W' => {
           write_to_file(state, ui, selection, ch, clean)
         },
+        'b' | 'B' => {
+          manage_buffers(state, ui, clean_command, selection, ch, clean)?;
+          Ok(false)
+        },
         // Print commands
         'p' | 'n' | 'l' => {
           let sel = interpret_selection(&state, selection, state.selection)?;
@@ -210,6 +243,10 @@ pub(crate) fn run(
           paste(state, &mut pflags, clean_command, selection, ch, clean)?;
           Ok(false)
         },
+        'Y' => { // List the clipboard ring
+          list_clipboards(state, ui, selection, clean)?;
+          Ok(false)
+        },
         'U' => {
           manage_history(state, ui, selection, clean)?;
           Ok(false)
@@ -233,7 +270,7 @@ pub(crate) fn run(
         },
         // Pattern commands
         's' => {
-          substitute(state, &mut pflags, clean_command, selection, tail)?;
+          substitute(state, ui, &mut pflags, clean_command, selection, tail)?;
           Ok(false)
         },
         'g' | 'v' | 'G' | 'V' => {
@@ -269,15 +306,45 @@ pub(crate) fn run(
           let args: Vec<&str> = args.collect();
           match state.macro_getter.get_macro(macro_name)? {
             Some(m) => {
+              if let Some(selection) = given_selection {
+                state.selection = selection;
+              }
+              // Snapshotting is handled according to the macro's configuration
+              state.private_run_macro(
+                ui,
+                m,
+                &args,
+                clean_command,
+                recursion_depth+1,
+              )
+            },
+            None => Err(EdError::MacroUndefined(macro_name.to_owned())),
+          }?;
+          Ok(false)
+        },
+        '@' => {
+          let given_selection = if selection.is_some() {
+            let s = interpret_selection(state, selection, state.selection)?;
+            state.history.current().verify_selection(s)?;
+            Some(s)
+          }
+          else {
+            None
+          };
+          // Same sloppy argument parsing as for macros
+          let mut args = clean.split(' ');
+          let plugin_name = args.next().unwrap_or("");
+          let args: Vec<&str> = args.collect();
+          // Copy out the reference, so the plugin can borrow state mutably
+          let plugin_getter = state.plugin_getter;
+          match plugin_getter.get_plugin(plugin_name)? {
+            Some(p) => {
               // Before disabling snapshotting, create one for this command
               state.history.snapshot(clean_command.into());
-              // Disable undo snapshotting during macro execution
+              // Squash the plugin's modifications into that snapshot
               let orig_dont_snapshot = state.history.dont_snapshot;
               state.history.dont_snapshot = true;
-              if let Some(selection) = given_selection {
-                state.selection = selection;
-              }
-              let res = state.private_run_macro(ui, m, &args, recursion_depth+1);
+              let res = p.run(state, ui, given_selection, &args);
               // Re-enable snapshotting after
               state.history.dont_snapshot = orig_dont_snapshot;
               // If snapshotting was originally enabled we should handle if no
@@ -285,12 +352,15 @@ pub(crate) fn run(
               if !orig_dont_snapshot { state.history.dedup_present(); }
               res
             },
-            None => Err(EdError::MacroUndefined(macro_name.to_owned())),
+            None => Err(EdError::PluginUndefined(plugin_name.to_owned())),
           }?;
           Ok(false)
         },
+        // Hand any other command to the command extender
         _cmd => {
-          Err(EdError::CommandUndefined(ch))
+          // Copy out the reference, so the extender can borrow state mutably
+          let extender = state.command_extender;
+          extender.run_command(state, ui, &mut pflags, selection, ch, clean)
         }
       }
     }
@@ -5,6 +5,8 @@ use crate::{
   EdError,
   Result,
 };
+use super::RegexFlags;
+use crate::regex_engine::RegexEngine;
 
 pub (super) fn get_tag(
   buffer: &Buffer,
@@ -23,38 +25,37 @@ pub(super) enum Direction {
   Forwards,
   Backwards,
 }
+// Returns the 1-indexed matching line and if the search wrapped around the
+// buffer to find it
 pub(super) fn get_matching(
   buffer: &Buffer,
+  engine: &dyn RegexEngine,
   pattern: &str,
+  flags: RegexFlags,
   curr_line: usize,
   direction: Direction,
-) -> Result<usize> {
+  wrap: bool,
+) -> Result<(usize, bool)> {
   buffer.verify_line(curr_line)?;
-  use regex::RegexBuilder;
-  let regex = RegexBuilder::new(pattern)
-    .multi_line(true)
-    .build()
-    .map_err(|e|EdError::regex_error(e,pattern))
-  ?;
-  let distance = match direction {
-    Direction::Forwards => buffer.len() - curr_line,
-    Direction::Backwards => curr_line - 1,
+  let regex = engine.compile(pattern, flags)?;
+  // Lines before wrapping, excluding current line, followed by the lines after
+  // wrapping (if wrapping), ending with current line. Each with if wrapped.
+  let len = buffer.len();
+  let lines: Box<dyn Iterator<Item = (usize, bool)>> = match direction {
+    Direction::Forwards => Box::new(
+      (curr_line + 1 ..= len).map(|l| (l, false))
+        .chain((1 ..= if wrap { curr_line } else { 0 }).map(|l| (l, true)))
+    ),
+    Direction::Backwards => Box::new(
+      (1 .. curr_line).rev().map(|l| (l, false))
+        .chain((if wrap { curr_line } else { len + 1 } ..= len).rev()
+          .map(|l| (l, true))
+        )
+    ),
   };
-  // Since the range must be positive/incrementing we subtract index from
-  // buffer.len() to iterate backwards
-  for index in 0 .. distance { match direction {
-    Direction::Forwards => {
-      // Conversion to 0 indexed (-1) and skipping current line (+1) negate
-      if regex.is_match(&(buffer[curr_line + index].text)) {
-        return Ok(curr_line + 1 + index); // +1 to return 1-indexed
-      }
-    },
-    Direction::Backwards => {
-      // Where conversion negated before it now stacks into -2
-      if regex.is_match(&(buffer[curr_line - 2 - index].text)) {
-        return Ok(curr_line - 1 - index); // Only -1 to return 1-indexed
-      }
-    },
-  }}
+  for (line, wrapped) in lines {
+    // Convert to 0-indexed when indexing the buffer
+    if regex.is_match(&buffer[line - 1].text)? { return Ok((line, wrapped)); }
+  }
   Err(EdError::RegexNoMatch(pattern.to_owned()))
 }
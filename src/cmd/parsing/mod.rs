@@ -9,3 +9,7 @@ mod path;
 pub use path::*;
 mod flags;
 pub use flags::*;
+mod register;
+pub use register::*;
+mod regex_flags;
+pub use regex_flags::*;
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use crate::error::*;
+use crate::Ed;
+
+/// The flags that configure how a regex matches
+///
+/// Given after the closing separator of a regex, as any combination of the
+/// below. (Enclosed in braces where a command may follow, as in `/re/{I}p`.)
+/// - `I` to match case insensitively
+/// - `x` for verbose mode, ignoring whitespace and allowing `#` comments
+/// - `s` to let `.` match newlines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegexFlags {
+  /// `I`, match case insensitively
+  pub case_insensitive: bool,
+  /// `x`, ignore whitespace and allow comments in the regex
+  pub verbose: bool,
+  /// `s`, let `.` match newlines
+  pub dot_matches_newline: bool,
+}
+impl RegexFlags {
+  /// The characters for all the regex flags
+  pub const FLAGS: &'static str = "Ixs";
+
+  /// Parse regex flags from the given input, erroring on any other flags
+  pub fn parse(input: &str) -> Result<Self> {
+    let mut flags = super::parse_flags(input, Self::FLAGS)?;
+    Ok(Self::take(&mut flags))
+  }
+  /// Take the regex flags out of the output from [`super::parse_flags`]
+  ///
+  /// The flag list given to it must have included [`RegexFlags::FLAGS`].
+  pub(crate) fn take(flags: &mut HashMap<char, bool>) -> Self {
+    Self{
+      case_insensitive: flags.remove(&'I').unwrap_or(false),
+      verbose: flags.remove(&'x').unwrap_or(false),
+      dot_matches_newline: flags.remove(&'s').unwrap_or(false),
+    }
+  }
+}
+
+// Parses regex flags enclosed in braces from start of input
+//
+// Used where a command may follow a regex, since the flag characters are also
+// commands and could otherwise be mistaken for one. No command starts with '{',
+// so this doesn't change the meaning of any other input. Returns the flags and
+// how many bytes of input they used, which is zero if input doesn't start with
+// a braced flag set.
+pub(crate) fn parse_braced_regex_flags(
+  input: &str,
+) -> Result<(RegexFlags, usize)> {
+  let end = match input.strip_prefix('{').and_then(|x| x.find('}')) {
+    Some(end) => end,
+    None => return Ok((RegexFlags::default(), 0)),
+  };
+  // The offsets are 1 off, as the '{' is stripped
+  Ok((RegexFlags::parse(&input[1 ..= end])?, end + 2))
+}
+
+// Replaces an empty regex with the previous regex given
+//
+// Flags given with the empty regex are added to the previous regex's flags.
+// Errors if no previous regex exists.
+pub(crate) fn reuse_pattern(
+  state: &Ed<'_>,
+  pattern: &str,
+  flags: RegexFlags,
+) -> Result<(String, RegexFlags)> {
+  if !pattern.is_empty() { return Ok((pattern.to_owned(), flags)); }
+  match &state.prev_pattern {
+    None => Err(EdError::DefaultPatternUnset),
+    Some((prev, prev_flags)) => Ok((prev.clone(), RegexFlags{
+      case_insensitive: flags.case_insensitive || prev_flags.case_insensitive,
+      verbose: flags.verbose || prev_flags.verbose,
+      dot_matches_newline: flags.dot_matches_newline ||
+        prev_flags.dot_matches_newline,
+    })),
+  }
+}
+// As reuse_pattern, but also saves the resulting regex as the previous regex
+pub(crate) fn resolve_pattern(
+  state: &mut Ed<'_>,
+  pattern: &str,
+  flags: RegexFlags,
+) -> Result<(String, RegexFlags)> {
+  let resolved = reuse_pattern(state, pattern, flags)?;
+  state.prev_pattern = Some(resolved.clone());
+  Ok(resolved)
+}
@@ -0,0 +1,17 @@
+use crate::error::*;
+
+/// Parses out a register name, given as `"<name>`, from the start of input
+///
+/// Returns the register name (if any) and the remaining input.
+pub fn parse_register(input: &str) -> Result<(Option<char>, &str)> {
+  match input.strip_prefix('"') {
+    None => Ok((None, input)),
+    Some(rest) => {
+      let mut chars = rest.chars();
+      match chars.next() {
+        Some(ch) if ch.is_ascii_alphanumeric() => Ok((Some(ch), chars.as_str())),
+        _ => Err(EdError::RegisterInvalid(input.to_owned())),
+      }
+    },
+  }
+}
@@ -4,6 +4,12 @@
 
 use crate::error::*;
 use crate::Ed;
+use super::{
+  RegexFlags,
+  parse_braced_regex_flags,
+  reuse_pattern,
+  resolve_pattern,
+};
 
 // A struct to formalise all the kinds of indices
 #[derive(PartialEq, Debug)]
@@ -12,8 +18,8 @@ pub enum Ind <'a> {
   BufferLen,
   Literal(usize),
   Tag(char),
-  Pattern(&'a str),
-  RevPattern(&'a str),
+  Pattern(&'a str, RegexFlags),
+  RevPattern(&'a str, RegexFlags),
   Add(Box<Ind<'a>>, usize),
   Sub(Box<Ind<'a>>, usize),
 }
@@ -38,9 +44,12 @@ pub fn parse_index(
   let mut end = None;
   let mut state = State::Default(0);
   let mut current_ind = None;
+  // Set to skip over regex flags, which are parsed when the regex ends
+  let mut skip_until = 0;
   // Loop over chars and parse
   let iter = input.char_indices();
   for (i, ch) in iter {
+    if i < skip_until { continue; }
     // Handle based on state
     match state {
       // If a state change is coming, populate current ind and make the change
@@ -143,8 +152,11 @@ pub fn parse_index(
               unrelated_index: input[start-1..i+1].to_owned(),
             }
           )}
-          current_ind = Some(Ind::Pattern(&input[start .. i]));
-          state = State::Default( i + 1 );
+          let (flags, flags_len) =
+            parse_braced_regex_flags(&input[i + 1 ..])?;
+          current_ind = Some(Ind::Pattern(&input[start .. i], flags));
+          skip_until = i + 1 + flags_len;
+          state = State::Default(skip_until);
         }
       },
       // Same as pattern with different end char
@@ -156,8 +168,11 @@ pub fn parse_index(
               unrelated_index: input[start-1..i+1].to_owned(),
             }
           )}
-          current_ind = Some(Ind::RevPattern(&input[start .. i]));
-          state = State::Default( i + 1 );
+          let (flags, flags_len) =
+            parse_braced_regex_flags(&input[i + 1 ..])?;
+          current_ind = Some(Ind::RevPattern(&input[start .. i], flags));
+          skip_until = i + 1 + flags_len;
+          state = State::Default(skip_until);
         }
       },
       // For Offset we never return to Default, since the only state valid after a non-normal state is Offset
@@ -276,6 +291,35 @@ pub fn parse_selection(
   }
 }
 
+// Saves the regexes given in pattern indices as the previous regex
+//
+// Needed since interpretation doesn't get mutable access to state. Run before
+// interpreting, so empty patterns can reuse a regex given earlier in the
+// selection.
+pub(crate) fn remember_patterns(
+  state: &mut Ed<'_>,
+  selection: &Option<Sel<'_>>,
+) -> Result<()> {
+  fn remember(state: &mut Ed<'_>, index: &Ind<'_>) -> Result<()> {
+    match index {
+      Ind::Pattern(pattern, flags) | Ind::RevPattern(pattern, flags) => {
+        resolve_pattern(state, pattern, *flags)?;
+      },
+      Ind::Add(inner, _) | Ind::Sub(inner, _) => remember(state, inner)?,
+      _ => (),
+    }
+    Ok(())
+  }
+  match selection {
+    Some(Sel::Lone(ind)) => remember(state, ind),
+    Some(Sel::Pair(ind1, ind2)) => {
+      remember(state, ind1)?;
+      remember(state, ind2)
+    },
+    None => Ok(()),
+  }
+}
+
 // Interprets index struct into 1-indexed usize.
 // (1-indexed so append operations can append to line 0 to insert before line 1)
 // Should not be able to return a index bigger than history.len().
@@ -293,20 +337,37 @@ pub fn interpret_index(
     // These return values are 0 indexed like the rest of the Buffer API
     // Subtract/add 1 on input/output
     Ind::Tag(tag) => super::get_tag(state.history.current(), tag),
-    Ind::Pattern(pattern) =>
+    // Empty patterns reuse the previous regex
+    Ind::Pattern(pattern, flags) => {
+      let (pattern, flags) = reuse_pattern(state, pattern, flags)?;
       super::get_matching(
         state.history.current(),
-        pattern,
+        state.regex_engine,
+        &pattern,
+        flags,
         old_selection,
         super::Direction::Forwards,
-      ),
-    Ind::RevPattern(pattern) =>
+        state.wrap_search,
+      ).map(|(line, wrapped)| {
+        if wrapped { state.search_wrapped.set(true); }
+        line
+      })
+    },
+    Ind::RevPattern(pattern, flags) => {
+      let (pattern, flags) = reuse_pattern(state, pattern, flags)?;
       super::get_matching(
         state.history.current(),
-        pattern,
+        state.regex_engine,
+        &pattern,
+        flags,
         old_selection,
-        super::Direction::Backwards
-      ),
+        super::Direction::Backwards,
+        state.wrap_search,
+      ).map(|(line, wrapped)| {
+        if wrapped { state.search_wrapped.set(true); }
+        line
+      })
+    },
     // These are relative to the prior, so have no indexing per-se
     Ind::Add(inner, offset) => {
       let inner = interpret_index(state, *inner, old_selection)?;
@@ -13,17 +13,15 @@ fn mark_matching(
   state: &mut Ed<'_>,
   selection: (usize, usize),
   pattern: &str,
+  regex_flags: RegexFlags,
   inverse: bool,
   recursion_depth: usize,
 ) -> Result<()> {
-  use regex::RegexBuilder;
+  // Empty patterns reuse the previous regex
+  let (pattern, regex_flags) = resolve_pattern(state, pattern, regex_flags)?;
   let buffer = state.history.current();
   buffer.verify_selection(selection)?;
-  let regex = RegexBuilder::new(pattern)
-    .multi_line(true)
-    .build()
-    .map_err(|e| EdError::regex_error(e, pattern))
-  ?;
+  let regex = state.regex_engine.compile(&pattern, regex_flags)?;
   let mut match_found = false;
   for (i, line) in buffer.iter().enumerate() {
     let mut matched_vec = line.matched.borrow_mut();
@@ -34,7 +32,7 @@ fn mark_matching(
     if i >= selection.0 -1 && i < selection.1 {
       // When we actually match we enlarge the vec to the needed length, adding
       // false to fill out, to enable writing true onto current index.
-      if regex.is_match(&(line.text)) ^ inverse {
+      if regex.is_match(&(line.text))? ^ inverse {
         matched_vec.resize(recursion_depth, false);
         matched_vec.push(true);
         match_found = true;
@@ -42,7 +40,7 @@ fn mark_matching(
     }
   }
   if !match_found {
-    Err(EdError::RegexNoMatch(pattern.to_owned()))
+    Err(EdError::RegexNoMatch(pattern))
   } else {
     Ok(())
   }
@@ -85,8 +83,11 @@ pub fn global(
   if expressions.len() < 2 {
     return Err( EdError::ArgumentsWrongNr{expected: "2 or more".into(), received: expressions.len()} );
   }
+  // Regex flags may be given in braces before the first command
+  let (regex_flags, flags_len) = parse_braced_regex_flags(&expressions[1])?;
+  expressions[1].replace_range(..flags_len, "");
   // We first try to mark all matching lines, to tell if there is any issue
-  mark_matching(state, selection, &expressions[0], command == 'v', recursion_depth + 1)?;
+  mark_matching(state, selection, &expressions[0], regex_flags, command == 'v', recursion_depth + 1)?;
   // Then we get the script to run against them, if not already given
   // First grab commands given on command line
   let mut commands: Vec<String> = expressions.split_off(1).iter().map(|s| s.to_string()).collect();
@@ -120,7 +121,10 @@ pub fn global(
     };
     state.selection = (index, index);
     loop {
-      if state.private_get_and_run_command(&mut scripted, recursion_depth + 1)? {
+      if state.private_get_and_run_scripted_command(
+        &mut scripted,
+        recursion_depth + 1,
+      )? {
         break;
       }
     }
@@ -142,12 +146,10 @@ pub fn global_interactive(
   if expressions.len() != 2 {
     return Err( EdError::ArgumentsWrongNr{expected: "2".into(), received: expressions.len()} );
   }
-  if !expressions[1].is_empty() && expressions[1] != "\n" {
-    return Err(EdError::FlagUndefined(expressions[1].chars().next().unwrap()));
-  }
+  let regex_flags = RegexFlags::parse(&expressions[1])?;
 
   // Mark first, to check if the expression is valid
-  mark_matching(state, selection, &expressions[0], command == 'V', recursion_depth + 1)?;
+  mark_matching(state, selection, &expressions[0], regex_flags, command == 'V', recursion_depth + 1)?;
   // With all data gathered we fetch and iterate over the lines
   while let Some(index) = get_marked(state, recursion_depth + 1) {
     // Print the line, so the user knows what they are changing
@@ -166,7 +168,10 @@ pub fn global_interactive(
     };
     state.selection = (index, index);
     loop {
-      if state.private_get_and_run_command(&mut scripted, recursion_depth + 1)? {
+      if state.private_get_and_run_scripted_command(
+        &mut scripted,
+        recursion_depth + 1,
+      )? {
         break;
       }
     }
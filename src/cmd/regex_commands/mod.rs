@@ -6,6 +6,9 @@ pub use substitute::*;
 mod global;
 pub use global::*;
 
+mod preview;
+pub use preview::*;
+
 /// Takes input str, returns string with escapes interpreted
 fn substitute_escape(input: &str) -> String {
   let mut out = String::new();
@@ -40,9 +43,48 @@ fn substitute_escape(input: &str) -> String {
   out
 }
 
+/// Takes GNU Ed style replacement, returns it in the regex crate's syntax
+///
+/// Converts `&` into the whole match and `\1` through `\9` into capture groups,
+/// while escaping the regex crate's `$` group syntax.
+fn gnu_substitute_escape(input: &str) -> String {
+  let mut out = String::new();
+  let mut escaped = false;
+
+  for ch in input.chars() {
+    if escaped {
+      match ch {
+        'n' => out.push('\n'),
+        'r' => out.push('\r'),
+        't' => out.push('\t'),
+        '$' => out.push_str("$$"),
+        c if c.is_ascii_digit() => {
+          out.push_str("${");
+          out.push(c);
+          out.push('}');
+        },
+        // Anything else escaped is taken literally, including '&' and '\\'
+        c => out.push(c),
+      }
+      escaped = false;
+    }
+    else {
+      match ch {
+        '\\' => escaped = true,
+        '&' => out.push_str("${0}"),
+        '$' => out.push_str("$$"),
+        c => out.push(c),
+      }
+    }
+  }
+  // A trailing backslash has nothing to escape, so keep it
+  if escaped { out.push('\\'); }
+  out
+}
+
 #[cfg(test)]
 mod test {
-  use super::substitute_escape;
+  use super::{substitute_escape, gnu_substitute_escape};
   #[test]
   fn test_backslash_escape() {
     // Double slash should be reduced into single slash,
@@ -55,4 +97,13 @@ mod test {
     let output = substitute_escape(input);
     assert_eq!(r"\n", &output); 
   }
+  #[test]
+  fn test_gnu_escape() {
+    // Whole match and capture groups
+    let output = gnu_substitute_escape(r"<&>\1\2");
+    assert_eq!("<${0}>${1}${2}", &output);
+    // Escaped ampersand and backslash, and the regex crate's syntax, are literal
+    let output = gnu_substitute_escape(r"\&\\$1");
+    assert_eq!(r"&\$$1", &output);
+  }
 }
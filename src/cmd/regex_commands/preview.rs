@@ -0,0 +1,92 @@
+use super::*;
+use crate::LineMatch;
+
+// Concatenates the lines in the selection, as substitution operates on
+fn aggregate(buffer: &Buffer, selection: (usize, usize)) -> Result<String> {
+  buffer.verify_selection(selection)?;
+  let mut agg = String::new();
+  for line in &buffer[selection.0 - 1 .. selection.1] {
+    agg.push_str(&line.text);
+  }
+  Ok(agg)
+}
+
+// Finds all matches for the regex within selection, without changing state
+//
+// Matches are found like `s` finds them, so a match may cover several lines.
+// Such matches are split up into one LineMatch per line they cover.
+pub fn find_matches(
+  state: &Ed<'_>,
+  pattern: &str,
+  flags: RegexFlags,
+  selection: (usize, usize),
+) -> Result<Vec<LineMatch>> {
+  // Empty patterns reuse the previous regex
+  let (pattern, flags) = reuse_pattern(state, pattern, flags)?;
+  let regex = state.regex_engine.compile(&pattern, flags)?;
+  let buffer = state.history.current();
+  let agg = aggregate(buffer, selection)?;
+  // The byte index in agg where each line starts
+  let mut line_starts = Vec::new();
+  let mut offset = 0;
+  for line in &buffer[selection.0 - 1 .. selection.1] {
+    line_starts.push(offset);
+    offset += line.text.len();
+  }
+  let mut matches = Vec::new();
+  for found in regex.find_iter(&agg, None) {
+    let found = found?;
+    // Only an empty match at the very end can be past all lines, skip it
+    if found.start == agg.len() { continue; }
+    // Index of the line holding the start of the match, 0-indexed from
+    // selection start
+    let mut i = line_starts.partition_point(|&x| x <= found.start) - 1;
+    loop {
+      let line_start = line_starts[i];
+      let line_end = line_starts.get(i + 1).copied().unwrap_or(agg.len());
+      matches.push(LineMatch{
+        line: selection.0 + i,
+        start: found.start.max(line_start) - line_start,
+        end: found.end.min(line_end) - line_start,
+      });
+      i += 1;
+      if i >= line_starts.len() || line_starts[i] >= found.end { break; }
+    }
+  }
+  Ok(matches)
+}
+
+// Returns the lines selection would be replaced by if the substitution was run
+//
+// Doesn't change state, so no undo snapshot is created and confirmation isn't
+// asked for. Errors like `s` if there is no match.
+pub fn preview_substitution(
+  state: &Ed<'_>,
+  s: &Substitution,
+  selection: (usize, usize),
+) -> Result<Vec<String>> {
+  // Resolve the pattern and replacement like `s` does
+  let (pattern, regex_flags) =
+    reuse_pattern(state, &s.pattern, s.regex_flags)?;
+  let s = Substitution{
+    pattern,
+    regex_flags,
+    substitute: resolve_replacement(state, &s.substitute)?,
+    global: s.global,
+    occurrence: s.occurrence,
+    confirm: s.confirm,
+    p: s.p,
+    n: s.n,
+    l: s.l,
+  };
+  let agg = aggregate(state.history.current(), selection)?;
+  // Without a UI all matches are replaced, so there will be a result
+  let after = substitute_text(
+    state.regex_engine,
+    None,
+    &agg,
+    &s,
+    state.gnu_substitute,
+  )?.unwrap_or(agg);
+  Ok(after.lines().map(|line| format!("{}\n", line)).collect())
+}
@@ -4,21 +4,20 @@ use super::*;
 //
 // Cuts out the selection, performs substitution and returns the index of the
 // selection's end after the substitution (since selection's length may change)
+// together with the lines before substitution (for the clipboard)
+//
+// Replaces the match number `s.occurrence` (1-indexed), and if `s.global` all
+// the matches after it too. If `s.confirm` the UI is asked about each match,
+// returning None if none was accepted.
 fn inner_substitute(
   history: &mut crate::History<crate::Buffer>,
-  clipboard: &mut Clipboard,
+  engine: &dyn RegexEngine,
+  ui: &mut dyn UI,
   command: &str, // Only because history needs it
   selection: (usize, usize),
-  pattern: &str,
-  substitute: &str,
-  global: bool,
-) -> Result<usize> {
-  use regex::RegexBuilder;
-  let regex = RegexBuilder::new(pattern)
-    .multi_line(true)
-    .build()
-    .map_err(|e| EdError::regex_error(e, pattern))
-  ?;
+  s: &Substitution,
+  gnu: bool,
+) -> Result<Option<(usize, Clipboard)>> {
   // Get a buffer view to verify selection and look for a match
   let buffer_view = history.current();
   buffer_view.verify_selection(selection)?;
@@ -26,10 +25,11 @@ fn inner_substitute(
   for line in &buffer_view[selection.0 - 1 .. selection.1] {
     agg.push_str(&line.text);
   }
-  if !regex.is_match(&agg) {
-    // Since we haven't modified any state we can safely return here
-    return Err(EdError::RegexNoMatch(pattern.to_owned()));
-  }
+
+  let after = match substitute_text(engine, Some(ui), &agg, s, gnu)? {
+    Some(x) => x,
+    None => return Ok(None),
+  };
 
   // If there was a match we can get a mutable access to the buffer
   // (creating an undo snapshot) and make the actual change.
@@ -38,17 +38,8 @@ fn inner_substitute(
   let mut tail = buffer.split_off(selection.1);
   let before = buffer.split_off(selection.0 - 1);
   // The before state should be saved in clipboard for all editing operations
-  *clipboard = (&*before).into();
+  let clipboard = (&*before).into();
 
-  // interpret escape sequences, then perform substitution
-  // We use data from buffer_view, since it cannot have changed
-  // (because we hold &mut Ed)
-  let replace = substitute_escape(substitute);
-  let after = if global {
-    regex.replace_all(&agg, replace).to_string()
-  } else {
-    regex.replace(&agg, replace).to_string()
-  };
   // Split on newlines and put into the buffer
   // The lines iterator doesn't care about if there is a last newline,
   // so that handles that edgecase.
@@ -63,11 +54,107 @@ fn inner_substitute(
   let end = buffer.len();
   // Put tail back on and return end
   buffer.append(&mut tail);
-  Ok(end)
+  Ok(Some((end, clipboard)))
+}
+
+// Performs the substitution on the given text, returning the resulting text
+//
+// Errors if there is no match to replace. If `s.confirm` and a UI is given it
+// is asked about each match, returning None if none was accepted. Without a UI
+// all matches are replaced.
+pub(super) fn substitute_text(
+  engine: &dyn RegexEngine,
+  mut ui: Option<&mut dyn UI>,
+  text: &str,
+  s: &Substitution,
+  gnu: bool,
+) -> Result<Option<String>> {
+  let regex = engine.compile(&s.pattern, s.regex_flags)?;
+  // interpret escape sequences, then perform substitution
+  let replace = if gnu { gnu_substitute_escape(&s.substitute) }
+    else { substitute_escape(&s.substitute) }
+  ;
+  let mut after = String::new();
+  let mut last_end = 0;
+  let mut match_found = false;
+  let mut replaced = false;
+  let mut confirm = s.confirm;
+  // Only look as far as needed, as each match found expands the replacement
+  let wanted = if s.global { usize::MAX } else { s.occurrence };
+  let matches = regex.find_iter(text, Some(&replace)).take(wanted);
+  for (i, found) in matches.enumerate() {
+    let found = found?;
+    if i + 1 < s.occurrence { continue; }
+    match_found = true;
+    if let (true, Some(ui)) = (confirm, ui.as_deref_mut()) {
+      // Show the line(s) holding the match, with the match's span in them
+      let start = text[.. found.start].rfind('\n').map(|x| x + 1).unwrap_or(0);
+      let end = text[found.end ..].find('\n')
+        .map(|x| found.end + x + 1)
+        .unwrap_or(text.len())
+      ;
+      match ui.confirm_substitution(
+        &text[start .. end],
+        (found.start - start, found.end - start),
+      )? {
+        Confirmation::Yes => (),
+        Confirmation::No => continue,
+        Confirmation::All => confirm = false,
+        Confirmation::Quit => break,
+      }
+    }
+    after.push_str(&text[last_end .. found.start]);
+    after.push_str(&found.replacement.unwrap_or_default());
+    last_end = found.end;
+    replaced = true;
+  }
+  if !match_found {
+    return Err(EdError::RegexNoMatch(s.pattern.clone()));
+  }
+  if !replaced { return Ok(None); }
+  after.push_str(&text[last_end..]);
+  Ok(Some(after))
+}
+
+// Separates out the number of the match to replace from GNU style flags
+//
+// The number must be one run of digits, either before or after the flags.
+// Returns the number (1 if none given) and the remaining flags.
+fn parse_occurrence(flags: &str) -> Result<(usize, String)> {
+  let rest = flags.trim_start_matches(|c: char| c.is_ascii_digit());
+  let (digits, rest) = if rest.len() < flags.len() {
+    (&flags[.. flags.len() - rest.len()], rest)
+  } else {
+    let rest = flags.trim_end_matches(|c: char| c.is_ascii_digit());
+    (&flags[rest.len() ..], rest)
+  };
+  if rest.contains(|c: char| c.is_ascii_digit()) {
+    return Err(EdError::SubstituteOccurrenceInvalid(flags.to_owned()));
+  }
+  if digits.is_empty() { return Ok((1, rest.to_owned())); }
+  match digits.parse::<usize>() {
+    Ok(occurrence) if occurrence > 0 => Ok((occurrence, rest.to_owned())),
+    _ => Err(EdError::SubstituteOccurrenceInvalid(digits.to_owned())),
+  }
+}
+
+// In GNU mode a lone '%' repeats the previous substitution's replacement
+pub(super) fn resolve_replacement(
+  state: &Ed<'_>,
+  replacement: &str,
+) -> Result<String> {
+  if !state.gnu_substitute || replacement != "%" {
+    return Ok(replacement.to_owned());
+  }
+  match &state.prev_s {
+    None => Err(EdError::DefaultSArgsUnset),
+    Some(s) => Ok(s.substitute.clone()),
+  }
 }
 
 pub fn substitute(
   state: &mut Ed<'_>,
+  ui: &mut dyn UI,
   pflags: &mut PrintingFlags,
   command: &str,
   selection: Option<Sel<'_>>,
@@ -85,20 +172,25 @@ pub fn substitute(
         pflags.p = s.p;
         pflags.n = s.n;
         pflags.l = s.l;
-        let end = inner_substitute(
+        let substituted = inner_substitute(
           &mut state.history,
-          &mut state.clipboard,
+          state.regex_engine,
+          ui,
           command,
           selection,
-          &s.pattern,
-          &s.substitute,
-          s.global,
+          s,
+          state.gnu_substitute,
         )?;
         // If we have deleted the whole selection we start sel at end,
         // in order to select line before the deleted lines. (min(end))
         // If end is smaller than 1 we have deleted to start of
         // buffer, then we use (1,0). (max(1))
-        state.selection = (selection.0.min(end).max(1), end);
+        // (If no replacement was confirmed the selection is unchanged)
+        state.selection = selection;
+        if let Some((end, clipboard)) = substituted {
+          state.selection = (selection.0.min(end).max(1), end);
+          state.set_clipboard(clipboard);
+        }
       }
     }
   }
@@ -107,34 +199,55 @@ pub fn substitute(
     if expressions.len() != 3 {
       return Err( EdError::ArgumentsWrongNr{expected: "none or 3".into(), received: expressions.len()} );
     }
-    let mut flags = parse_flags(&(expressions[2]), "gpnl")?;
+    // In GNU mode the flags may include which match to replace
+    let (occurrence, flags) = if state.gnu_substitute {
+      parse_occurrence(&expressions[2])?
+    } else {
+      (1, expressions[2].to_string())
+    };
+    let mut flags = parse_flags(&flags, "gcpnlIxs")?;
+    let regex_flags = RegexFlags::take(&mut flags);
     let g = flags.remove(&'g').unwrap();
+    let confirm = flags.remove(&'c').unwrap();
     pflags.p = flags.remove(&'p').unwrap();
     pflags.n = flags.remove(&'n').unwrap();
     pflags.l = flags.remove(&'l').unwrap();
-    let end = inner_substitute(
+    let replacement = resolve_replacement(state, &expressions[1])?;
+    // Empty patterns reuse the previous regex
+    let (pattern, regex_flags) =
+      resolve_pattern(state, &expressions[0], regex_flags)?;
+    let s = Substitution{
+      pattern,
+      regex_flags,
+      substitute: replacement,
+      global: g,
+      occurrence,
+      confirm,
+      p: pflags.p,
+      n: pflags.n,
+      l: pflags.l,
+    };
+    let substituted = inner_substitute(
       &mut state.history,
-      &mut state.clipboard,
+      state.regex_engine,
+      ui,
       command,
       selection,
-      &expressions[0],
-      &expressions[1],
-      g,
+      &s,
+      state.gnu_substitute,
     )?;
     // If we have deleted the whole selection we start sel at end,
     // in order to select line before the deleted lines. (min(end))
     // If end is smaller than 1 we have deleted whole buffer,
     // then we use (1,0). (max(1))
-    state.selection = (selection.0.min(end).max(1), end);
+    // (If no replacement was confirmed the selection is unchanged)
+    state.selection = selection;
+    if let Some((end, clipboard)) = substituted {
+      state.selection = (selection.0.min(end).max(1), end);
+      state.set_clipboard(clipboard);
+    }
     // If that was valid we save all the arguments to support lone 's'
-    state.prev_s = Some(Substitution{
-      pattern: expressions[0].to_string(),
-      substitute: expressions[1].to_string(),
-      global: g,
-      p: pflags.p,
-      n: pflags.n,
-      l: pflags.l,
-    });
+    state.prev_s = Some(s);
   }
   Ok(())
 }
@@ -1,5 +1,38 @@
 use super::*;
 
+// Parse the optional nr of steps given after the undo sub-command
+fn parse_steps(arguments: &str) -> Result<usize> {
+  let steps = if !arguments.is_empty() {
+    arguments
+      .parse::<usize>()
+      .map_err(|_| EdError::UndoStepsNotInt(arguments.to_owned()))
+    ?
+  } else { 1 };
+  if steps == 0 { return Err(EdError::NoOp); }
+  Ok(steps)
+}
+
+// Find the nearest snapshot after/before the viewed one with a matching label
+fn find_label(
+  history: &crate::History<Buffer>,
+  pattern: &str,
+  forwards: bool,
+) -> Result<usize> {
+  let regex = regex::Regex::new(pattern)
+    .map_err(|e| EdError::regex_error(e, pattern))
+  ?;
+  let viewed = history.viewed_i();
+  let labels = history.snapshots().iter().enumerate()
+    .map(|(i, (label, _))| (i, label));
+  let found = if forwards {
+    labels.skip(viewed + 1).find(|(_, l)| regex.is_match(l))
+  } else {
+    labels.take(viewed).rev().find(|(_, l)| regex.is_match(l))
+  };
+  found.map(|(i, _)| i)
+    .ok_or_else(|| EdError::RegexNoMatch(pattern.to_owned()))
+}
+
 pub fn undo(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
@@ -11,36 +44,69 @@ pub fn undo(
   // ie. relative AND shorthand for start and end of history
   let mut arg_iter = arguments.chars();
   match arg_iter.next() {
-    // Go to point in history TODO
-    //Some('*') => {},
+    // Go to absolute index in history
+    Some('*') => {
+      let index = arguments[1..]
+        .parse::<usize>()
+        .map_err(|_| EdError::UndoIndexNotInt(arguments[1..].to_owned()))
+      ?;
+      let label = state.history.set_viewed_i(index)?;
+      ui.print_message(&format!(
+        "Moved to index {} in history, right after {}.",
+        index,
+        label,
+      ))?;
+    },
+    // Search forwards/backwards through history labels
+    Some(ch @ '/') | Some(ch @ '?') => {
+      let expressions = parse_expressions(arguments)?;
+      // Allow, but don't require, closing the regex
+      if
+        expressions.len() > 2 ||
+        expressions.get(1).is_some_and(|e| !e.is_empty())
+      {
+        return Err(EdError::ArgumentsWrongNr{
+          expected: "1".into(),
+          received: expressions.len(),
+        });
+      }
+      let index = find_label(
+        &state.history,
+        &expressions[0],
+        ch == '/',
+      )?;
+      let label = state.history.set_viewed_i(index)?;
+      ui.print_message(&format!(
+        "Moved to index {} in history, right after {}.",
+        index,
+        label,
+      ))?;
+    },
     // Undo/redo (negative is redo
     Some('-') => {
-      let steps = if arg_iter.next().is_some() {
-        arguments[1..]
-          .parse::<usize>()
-          .map_err(|_| EdError::UndoStepsNotInt(arguments[1..].to_owned()))
-        ?
-      } else { 1 };
-      if steps == 0 { return Err(EdError::NoOp); }
-      let new_pos = state.history.set_viewed_i(state.history.viewed_i() + steps)?;
+      let steps = parse_steps(&arguments[1..])?;
+      let new_pos = state.history.redo(steps)?;
       ui.print_message(&format!(
         "Redid {} operation(s) to right after {}.",
         steps,
         new_pos,
       ))?;
     },
-    x => {
-      let steps = if x.is_some() {
-        arguments
-          .parse::<usize>()
-          .map_err(|_| EdError::UndoStepsNotInt(arguments.to_owned()))
-        ?
-      } else { 1 };
-      if steps == 0 { return Err(EdError::NoOp); }
-      if state.history.viewed_i() < steps {
-        return Err(EdError::UndoIndexNegative{relative_undo_limit: state.history.viewed_i()});
-      }
-      let new_pos = state.history.set_viewed_i(state.history.viewed_i() - steps)?;
+    // Switch to the end of a newer/older branch of history
+    Some(ch @ '>') | Some(ch @ '<') => {
+      let steps = parse_steps(&arguments[1..])?;
+      let new_pos = state.history.switch_branch(
+        if ch == '>' { steps as isize } else { -(steps as isize) }
+      )?;
+      ui.print_message(&format!(
+        "Switched {} branch(es) to right after {}.",
+        steps,
+        new_pos,
+      ))?;
+    },
+    _ => {
+      let steps = parse_steps(arguments)?;
+      let new_pos = state.history.undo(steps)?;
       ui.print_message(&format!(
         "Undid {} operation(s) to right after {}.",
         steps,
@@ -51,6 +117,45 @@ pub fn undo(
   Ok(())
 }
 
+// Parse the position given to `U`, either an absolute index or `$` optionally
+// followed by `-` and a nr of steps back from the end of the viewed branch
+fn parse_history_position(
+  state: &Ed<'_>,
+  position: &str,
+) -> Result<Option<usize>> {
+  let position = position.trim();
+  if position.is_empty() { return Ok(None); }
+  let parse = |x: &str| x.parse::<usize>()
+    .map_err(|_| EdError::UndoIndexNotInt(position.to_owned()))
+  ;
+  match position.strip_prefix('$') {
+    Some(relative) => {
+      let steps = match relative.strip_prefix('-') {
+        Some(steps) => parse(steps)?,
+        None if relative.is_empty() => 0,
+        None => return Err(EdError::UndoIndexNotInt(position.to_owned())),
+      };
+      let branch = state.history.current_branch();
+      let pos = branch.len() - 1;
+      if pos < steps {
+        return Err(EdError::UndoIndexNegative{relative_undo_limit: pos});
+      }
+      Ok(Some(branch[pos - steps]))
+    },
+    None => {
+      let index = parse(position)?;
+      if index >= state.history.len() {
+        return Err(EdError::UndoIndexTooBig{
+          index,
+          history_len: state.history.len(),
+          relative_redo_limit: state.history.len() - state.history.viewed_i() - 1,
+        });
+      }
+      Ok(Some(index))
+    },
+  }
+}
+
 pub fn manage_history(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
@@ -58,43 +163,75 @@ pub fn manage_history(
   tail: &str,
 ) -> Result<()> {
   if selection.is_some() {return Err(EdError::SelectionForbidden); }
-  // Some custom flags (or maybe arguments) should probably be added later
-  let mut _flags = parse_flags(tail, "")?;
-  // To enable undoing to an absolute "index" from start of editing the
-  // History struct must allow both accessing current index and the labels for
-  // all indices. Due to the data structure this will also require allowing
-  // access to the snapshotted states and effectively expose internal design.
+  // Flags come first, then the optional position to act around
+  let split = tail.find(|c: char| c.is_ascii_digit() || c == '$')
+    .unwrap_or(tail.len())
+  ;
+  let mut flags = parse_flags(&tail[..split], "aAbc")?;
+  let position = parse_history_position(state, &tail[split..])?;
 
-  // Figure out the history index slice for the nearest 10 snapshots
-  let i = state.history.viewed_i();
-  let view = state.history.snapshots();
-  // If in the first five snapshots we want the first 10
-  let history_indices = if i < 10 {
-    // Use .min(view.len()) to limit within valid slicing
-    0 .. 10.min(view.len())
-  }
-  // If in the last five snapshots we want the last 10
-  else if view.len().saturating_sub(10) <= i {
-    // Use saturating sub to avoid underflow
-    view.len().saturating_sub(10) .. view.len()
+  // Clearing history is a separate operation from printing it
+  if flags.remove(&'c').unwrap() {
+    let removed = match position {
+      Some(i) => state.history.truncate_before(i)?,
+      None => state.history.clear(),
+    };
+    ui.print_message(&format!(
+      "Removed {} snapshot(s) from history.",
+      removed,
+    ))?;
+    return Ok(());
   }
-  // Otherwise we want the 5 preceding, current and 4 following snapshots
-  // (Since none of the preceeding were true we can safely slice this)
-  else {
-    i - 5 .. i + 4
+
+  let i = state.history.viewed_i();
+  let branch = state.history.current_branch();
+  let history_indices = if flags.remove(&'b').unwrap() {
+    // List the last snapshot in every branch, so they can be switched between
+    state.history.branches()
+  } else {
+    // List the branch through the given position, defaulting to the viewed
+    // branch (which is all of history unless it is a tree)
+    let target = position.unwrap_or(i);
+    let branch = state.history.branch_of(target);
+    if flags.remove(&'A').unwrap() {
+      branch
+    } else {
+      // Figure out the history index slice for the nearest 10 snapshots
+      let pos = branch.iter().position(|x| *x == target).unwrap_or(0);
+      // If in the first five snapshots we want the first 10
+      let range = if pos < 10 {
+        // Use .min(branch.len()) to limit within valid slicing
+        0 .. 10.min(branch.len())
+      }
+      // If in the last five snapshots we want the last 10
+      else if branch.len().saturating_sub(10) <= pos {
+        // Use saturating sub to avoid underflow
+        branch.len().saturating_sub(10) .. branch.len()
+      }
+      // Otherwise we want the 5 preceding, current and 4 following snapshots
+      // (Since none of the preceeding were true we can safely slice this)
+      else {
+        pos - 5 .. pos + 4
+      };
+      branch[range].to_vec()
+    }
   };
 
   // Print it nicely
+  // (When listing branches we mark the one viewed, even if not at its end)
+  let viewed = if history_indices.contains(&i) { i }
+    else { *branch.last().unwrap_or(&i) }
+  ;
+  let absolute = flags.remove(&'a').unwrap();
   let saved = state.history.saved_i();
-  let mut tmp = String::new();
-  for hi in history_indices {
-    tmp.push_str(&format!(
-      "{} {} {}",
-      if hi == i { '>' } else { ' ' },
-      view[hi].0,
-      if Some(hi) == saved { "(saved)" } else { "" },
-    ));
-  }
-  ui.print_message(&tmp)?;
+  let view = state.history.snapshots();
+  let listing: Vec<String> = history_indices.iter().map(|hi| format!(
+    "{} {}{} {}",
+    if *hi == viewed { '>' } else { ' ' },
+    if absolute { format!("{}: ", hi) } else { String::new() },
+    view[*hi].0,
+    if Some(*hi) == saved { "(saved)" } else { "" },
+  )).collect();
+  ui.print_message(&listing.join("\n"))?;
   Ok(())
 }
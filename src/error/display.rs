@@ -49,6 +49,10 @@ impl std::fmt::Display for EdError {
       UnsavedChanges => write!(f,
         "Unsaved changes! Capitalise command to ignore.",
       ),
+      FileChanged(path) => write!(f,
+        "File `{}` was changed by something else! Repeat command to overwrite.",
+        path,
+      ),
       NoOp => write!(f,
         "That combination of command and arguments doesn't do anything.",
       ),
@@ -62,6 +66,14 @@ impl std::fmt::Display for EdError {
         index,
         history_len - 1,
       ),
+      UndoBranchTooFar{relative_limit} => write!(f,
+        "Tried to switch beyond existing branches of history.\nHighest valid nr of branch steps in that direction is {}.",
+        relative_limit,
+      ),
+      UndoTruncateViewed{index} => write!(f,
+        "Cannot remove history before index {}, as that would remove the viewed snapshot.",
+        index,
+      ),
       CommandEscapeForbidden(_path) => write!(f,
         "Command doesn't accept shell escapes. Use \\! if path begins with !.",
       ),
@@ -78,6 +90,11 @@ impl std::fmt::Display for EdError {
         regex,
         error,
       ),
+      RegexEngineError{regex, error} => write!(f,
+        "Regex `{}` failed! {}.",
+        regex,
+        error,
+      ),
       RegexNoMatch(regex) => write!(f,
         "No matches found for regex `{}`.",
         regex,
@@ -85,6 +102,10 @@ impl std::fmt::Display for EdError {
       PrintAfterWipe => write!(f,
         "Would print after deleting whole buffer, refusing to run command.",
       ),
+      HistoryInvalid(e) => write!(f,
+        "Persisted undo history is invalid: {}",
+        e,
+      ),
 
       DefaultFileUnset => write!(f,
         "Couldn't read default file as it hasn't been set. Set by opening file or with the `f` command.",
@@ -95,6 +116,9 @@ impl std::fmt::Display for EdError {
       DefaultSArgsUnset => write!(f,
         "Couldn't read default `s` arguments as they haven't been set. Run `s` with arguments first.",
       ),
+      DefaultPatternUnset => write!(f,
+        "Couldn't reuse the previous regex as none has been given yet. Give a non-empty regex first.",
+      ),
 
       IndexSpecialAfterStart{prior_index, special_index} => write!(f,
         "Special index character `{}` found after index `{}`.",
@@ -140,6 +164,14 @@ impl std::fmt::Display for EdError {
         "Failed to parse nr of changes to undo/redo `{}` as a number.",
         text,
       ),
+      UndoIndexNotInt(text) => write!(f,
+        "Failed to parse history index `{}` as a number.",
+        text,
+      ),
+      SubstituteOccurrenceInvalid(text) => write!(f,
+        "Failed to parse match to replace `{}` as a positive number.",
+        text,
+      ),
       ReflowNotInt{error: e, text: t} => write!(f,
         "Failed to parse nr of columns to reflow within `{}` as a number: {}",
         t,
@@ -149,6 +181,38 @@ impl std::fmt::Display for EdError {
         "Given macro `{}` is not defined.",
         macro_name,
       ),
+      PluginUndefined(plugin_name) => write!(f,
+        "Given plugin `{}` is not defined.",
+        plugin_name,
+      ),
+      BufferNotFound(buffer) => write!(f,
+        "No open buffer `{}`. Use `b` to list open buffers.",
+        buffer,
+      ),
+      BufferUnsaved(nr) => write!(f,
+        "Buffer {} has unsaved changes.\nSwitch to it and save, or capitalise the command to ignore this.",
+        nr,
+      ),
+      BufferLast => write!(f,
+        "Cannot close the only open buffer."
+      ),
+      RegisterInvalid(register) => write!(f,
+        "Invalid register given `{}`. Registers are named by a letter or digit after a `\"`.",
+        register,
+      ),
+      RegisterEmpty(register) => write!(f,
+        "Register `{}` is empty.",
+        register,
+      ),
+      ClipboardIndexNotInt(text) => write!(f,
+        "Failed to parse clipboard index `{}` as a number.",
+        text,
+      ),
+      ClipboardIndexTooBig{index, ring_len} => write!(f,
+        "Clipboard index {} is too big, only {} clipboard(s) are kept.",
+        index,
+        ring_len,
+      ),
 
       FlagDuplicate(flag) => write!(f,
         "Flag `{}` was given more than once.",
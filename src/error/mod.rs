@@ -102,12 +102,20 @@ pub enum EdError {
   // Command+argument+flag interpretation errors
   /// Unsaved changes when about to non-forcibly drop/delete buffer.
   UnsavedChanges,
+  /// The file about to be overwritten was changed by something else since it
+  /// was read or written.
+  /// Holds the path.
+  FileChanged(String),
   /// Selection and arguments were given that makes its command do nothing.
   NoOp,
   /// Tried to undo beyond start of history.
   UndoIndexNegative{relative_undo_limit: usize},
   /// Tried to redo past end of history.
   UndoIndexTooBig{index: usize, history_len: usize, relative_redo_limit: usize},
+  /// Tried to switch to a branch of history beyond the existing ones.
+  UndoBranchTooFar{relative_limit: usize},
+  /// Tried to remove the viewed snapshot when truncating history.
+  UndoTruncateViewed{index: usize},
   /// Tried to given shell escape where a file path is required.
   /// Holds given path string.
   CommandEscapeForbidden(String),
@@ -119,11 +127,17 @@ pub enum EdError {
   TagNoMatch(char),
   /// Any regex operation received an invalid regex or substitution.
   RegexInvalid{regex: String, error: regex::Error},
+  /// A regex engine other than the default failed to compile or run a regex.
+  /// Holds the regex and the engine's error message.
+  RegexEngineError{regex: String, error: String},
   /// Given regex found no match.
   /// Holds the used regex.
   RegexNoMatch(String),
   /// Flags asked to print after the whole buffer was deleted.
   PrintAfterWipe,
+  /// Persisted undo history couldn't be (de)serialized.
+  /// Holds the underlying error message.
+  HistoryInvalid(String),
 
   // Errors related to unset state variables
   /// Tried to get default shell command, but it isn't yet set
@@ -132,6 +146,8 @@ pub enum EdError {
   DefaultShellCommandUnset,
   /// Tried to get default `s` arguments, but it isn't yet set
   DefaultSArgsUnset,
+  /// Tried to reuse the previous regex, but none has been given yet
+  DefaultPatternUnset,
 
   // Index parsing errors
   /// Special index character found after start of index.
@@ -163,12 +179,40 @@ pub enum EdError {
   /// `u` or `U` command couldn't interpret nr of steps to undo/redo as integer.
   /// Holds given argument.
   UndoStepsNotInt(String),
+  /// `u` command received a non numeric history index to move to.
+  /// Holds given argument.
+  UndoIndexNotInt(String),
+  /// `s` command received an invalid number of the match to replace (GNU mode).
+  /// Holds given number.
+  SubstituteOccurrenceInvalid(String),
   /// `J` command received a non numeric number of columns to reflow within.
   /// Holds given argument.
   ReflowNotInt{error: String, text: String},
   /// The macro invoked wasn't found.
   /// Holds given macro name.
   MacroUndefined(String),
+  /// The plugin invoked wasn't found.
+  /// Holds given plugin name.
+  PluginUndefined(String),
+  /// `b` command received a buffer number that isn't open (or not a number).
+  /// Holds given argument.
+  BufferNotFound(String),
+  /// Tried to close or quit with unsaved changes in another buffer.
+  /// Holds the number of the buffer.
+  BufferUnsaved(usize),
+  /// Tried to close the only open buffer.
+  BufferLast,
+  /// Register name given isn't valid (only ascii alphanumeric are valid).
+  /// Holds given argument.
+  RegisterInvalid(String),
+  /// Tried to paste from a register that hasn't been written to.
+  /// Holds the register name.
+  RegisterEmpty(char),
+  /// `x` or `X` command received a non numeric clipboard ring index.
+  /// Holds given argument.
+  ClipboardIndexNotInt(String),
+  /// `x` or `X` command received a clipboard ring index without an entry.
+  ClipboardIndexTooBig{index: usize, ring_len: usize},
 
   // Flag parsing errors
   /// Same flag appears more than once.
@@ -21,6 +21,7 @@ impl std::cmp::PartialEq for EdError {
       (SelectionForbidden,SelectionForbidden) => true,
 
       (UnsavedChanges,UnsavedChanges) => true,
+      (FileChanged(a),FileChanged(b)) => a == b,
       (NoOp,NoOp) => true,
       (
         UndoIndexTooBig{index: a, history_len: b, relative_redo_limit: c},
@@ -28,6 +29,8 @@ impl std::cmp::PartialEq for EdError {
       ) => {
         a == d && b == e && c == f
       },
+      (UndoBranchTooFar{relative_limit: a},UndoBranchTooFar{relative_limit: b}) => a == b,
+      (UndoTruncateViewed{index: x},UndoTruncateViewed{index: y}) => x == y,
       (CommandEscapeForbidden(x),CommandEscapeForbidden(y)) => x == y,
       (TagInvalid(x),TagInvalid(y)) => x == y,
       (TagNoMatch(x),TagNoMatch(y)) => x == y,
@@ -37,12 +40,20 @@ impl std::cmp::PartialEq for EdError {
       ) => {
         a == c && b == d
       },
+      (
+        RegexEngineError{regex: a, error: b},
+        RegexEngineError{regex: c, error: d},
+      ) => {
+        a == c && b == d
+      },
       (RegexNoMatch(x),RegexNoMatch(y)) => x == y,
       (PrintAfterWipe,PrintAfterWipe) => true,
+      (HistoryInvalid(x),HistoryInvalid(y)) => x == y,
 
       (DefaultFileUnset,DefaultFileUnset) => true,
       (DefaultShellCommandUnset,DefaultShellCommandUnset) => true,
       (DefaultSArgsUnset,DefaultSArgsUnset) => true,
+      (DefaultPatternUnset,DefaultPatternUnset) => true,
 
       (
         IndexSpecialAfterStart{prior_index: a, special_index: b},
@@ -70,10 +81,25 @@ impl std::cmp::PartialEq for EdError {
       },
       (ScrollNotInt(x),ScrollNotInt(y)) => x == y,
       (UndoStepsNotInt(x),UndoStepsNotInt(y)) => x == y,
+      (UndoIndexNotInt(x),UndoIndexNotInt(y)) => x == y,
+      (SubstituteOccurrenceInvalid(x),SubstituteOccurrenceInvalid(y)) => x == y,
       (ReflowNotInt{error: a, text: b},ReflowNotInt{error: c, text: d}) => {
         a == c && b == d
       },
       (MacroUndefined(x),MacroUndefined(y)) => x == y,
+      (PluginUndefined(x),PluginUndefined(y)) => x == y,
+      (BufferNotFound(x),BufferNotFound(y)) => x == y,
+      (BufferUnsaved(x),BufferUnsaved(y)) => x == y,
+      (BufferLast,BufferLast) => true,
+      (RegisterInvalid(x),RegisterInvalid(y)) => x == y,
+      (RegisterEmpty(x),RegisterEmpty(y)) => x == y,
+      (ClipboardIndexNotInt(x),ClipboardIndexNotInt(y)) => x == y,
+      (
+        ClipboardIndexTooBig{index: a, ring_len: b},
+        ClipboardIndexTooBig{index: c, ring_len: d},
+      ) => {
+        a == c && b == d
+      },
 
       (FlagDuplicate(x),FlagDuplicate(y)) => x == y,
       (FlagUndefined(x),FlagUndefined(y)) => x == y,
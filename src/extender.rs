@@ -0,0 +1,64 @@
+//! Support for adding commands to Ed without modifying add-ed
+//!
+//! Re-exports the parsing helpers the built-in commands use, so that extension
+//! commands can behave like the native ones.
+
+use crate::{Ed, EdError, Result};
+use crate::ui::UI;
+
+pub use crate::cmd::{
+  PrintingFlags,
+  Sel,
+  Ind,
+  interpret_selection,
+  parse_flags,
+  parse_expressions,
+};
+
+/// Trait for handling commands that add-ed doesn't define
+///
+/// Whenever a command character isn't recognized by add-ed the command is
+/// handed to the [`Ed.command_extender`](crate::Ed::command_extender), which
+/// may either run it or return [`EdError::CommandUndefined`].
+///
+/// A ready implementation exists for `()`, which defines no commands.
+pub trait CommandExtender {
+  /// Run the given command
+  ///
+  /// - `pflags` are the printing flags to apply after the command. Set them
+  ///   from the tail with [`parse_flags`] to support `p`, `n` and `l`.
+  /// - `selection` is the parsed selection given, if any. Use
+  ///   [`interpret_selection`] to get the actual lines it refers to (with the
+  ///   default selection of your choice).
+  /// - `command` is the command character.
+  /// - `tail` is everything after the command character, without the trailing
+  ///   newline.
+  ///
+  /// Modifications should be made through `ed.history.current_mut()`, same as
+  /// the built-in commands, labelled with the full command if possible.
+  ///
+  /// Returns true if the command was to quit.
+  fn run_command(
+    &self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    pflags: &mut PrintingFlags,
+    selection: Option<Sel<'_>>,
+    command: char,
+    tail: &str,
+  ) -> Result<bool>;
+}
+
+impl CommandExtender for () {
+  fn run_command(
+    &self,
+    _ed: &mut Ed<'_>,
+    _ui: &mut dyn UI,
+    _pflags: &mut PrintingFlags,
+    _selection: Option<Sel<'_>>,
+    command: char,
+    _tail: &str,
+  ) -> Result<bool> {
+    Err(EdError::CommandUndefined(command))
+  }
+}
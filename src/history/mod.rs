@@ -0,0 +1,476 @@
+//! Module for history snapshotting and management.
+
+use crate::{EdError, Result};
+use std::fmt::Debug;
+
+#[cfg(feature = "serde")]
+mod serialization;
+
+/// A special type of Clone for [`History`]
+///
+/// Needed because [`History`] requires a Clone that re-uses as much memory as
+/// possible, while normal Clone is usually expected to create unrelated copies
+/// of data.
+pub trait Snapshot{
+  /// Create a memory efficient copy of self
+  ///
+  /// Beware, mutation of the created copy (if even possible) may modify the
+  /// original.
+  fn create_snapshot(&self) -> Self;
+}
+
+/// How [`History`] handles modifications made while viewing an earlier snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature="serde", serde(rename_all="lowercase"))]
+pub enum HistoryMode {
+  /// Linear history, where a snapshot reverting to the viewed snapshot is added
+  /// at the end of history before modifying. Inspired by
+  /// [this reasoning.](https://github.com/zaboople/klonk/blob/master/TheGURQ.md)
+  #[default]
+  Revert,
+  /// Branching history, like vim's undo tree. The modification is added as a
+  /// new child of the viewed snapshot, keeping all branches reachable.
+  Tree,
+}
+
+/// A history abstraction over generic objects used by add-ed.
+///
+/// Handles snapshotting and moving over the history of snapshots. By default
+/// uses a revert style of undo, see [`HistoryMode`] for the alternatives.
+///
+/// Every snapshot knows its parent, the snapshot it was created from. In
+/// [`HistoryMode::Revert`] that is always the preceding snapshot, so the
+/// history is a single branch. In [`HistoryMode::Tree`] it is whichever
+/// snapshot was viewed when it was created, so any number of branches may
+/// exist. Navigate them with [`History::undo`], [`History::redo`] and
+/// [`History::switch_branch`], or inspect them with [`History::parent`],
+/// [`History::children`], [`History::branches`] and
+/// [`History::current_branch`].
+///
+/// Automatically manages snapshot creation upon mutable access to the current
+/// point in history. Further allows pausing snapshot creation via
+/// [`History.dont_snapshot`] as well as manual snapshot creation via
+/// [`History.snapshot`] (for use during script/macro execution, to make each
+/// snapshot correspond to a user action).
+///
+/// If the `serde` feature is enabled `History<Buffer>` implements `Serialize`
+/// and `Deserialize`, deduplicating the line data shared between snapshots.
+/// (`dont_snapshot` isn't serialized, as it is runtime state.)
+#[derive(Clone, Debug)]
+pub struct History<T> where
+  T: Default + Debug + Snapshot + PartialEq,
+{
+  snapshots: Vec<(String, T)>,
+  // Index of the snapshot each snapshot was created from, None for the root
+  parents: Vec<Option<usize>>,
+  // Indices of the snapshots created from each snapshot, oldest first. Kept in
+  // sync with parents, to not have to search it when walking down a branch.
+  children: Vec<Vec<usize>>,
+  mode: HistoryMode,
+  viewed_i: usize,
+  saved_i: Option<usize>,
+  /// If true all calls to [`History::snapshot`] are ignored (including the
+  /// automatic call upon running `.current_mut()`).
+  ///
+  /// Intended for macro execution, when it would be confusing to create
+  /// multiple snapshots for what the user sees as a single action.
+  ///
+  /// (If a point in history is viewed a snapshot reverting to (or in
+  /// [`HistoryMode::Tree`] branching from) that point in history will be
+  /// created before mutable access no matter if this variable is set to true.)
+  pub dont_snapshot: bool,
+}
+impl<T> Default for History<T> where
+  T: Default + Debug + Snapshot + PartialEq,
+{
+  fn default() -> Self { Self::new() }
+}
+impl <T> History<T> where
+  T: Default + Debug + Snapshot + PartialEq,
+{
+  /// Create new [`History`] instance
+  ///
+  /// - Only an empty present state exists.
+  /// - Considered saved at initial empty state.
+  pub fn new() -> Self {
+    Self::with_mode(HistoryMode::default())
+  }
+  /// Create new [`History`] instance using the given [`HistoryMode`]
+  ///
+  /// Otherwise same as [`History::new`]. The mode cannot be changed afterwards.
+  pub fn with_mode(mode: HistoryMode) -> Self {
+    Self{
+      snapshots: vec![("Before reading in a file (empty)".to_owned(), T::default())],
+      parents: vec![None],
+      children: vec![Vec::new()],
+      mode,
+      viewed_i: 0,
+      saved_i: Some(0),
+      dont_snapshot: false,
+    }
+  }
+  /// Getter for the [`HistoryMode`] in use
+  pub fn mode(&self) -> HistoryMode {
+    self.mode
+  }
+
+  /// Get if the buffer is saved
+  ///
+  /// It aims to be true when the viewed buffer matches the data last saved.
+  /// If it is uncertain or difficult to track it will return false.
+  pub fn saved(&self) -> bool {
+    self.saved_i == Some(self.viewed_i)
+  }
+  /// Mark the currently viewed buffer state as saved
+  ///
+  /// If `dont_snapshot` is set this instead behaves as
+  /// `set_unsaved()`, as we cannot be sure a snapshot will exist
+  /// corresponding to the state in which the buffer was saved.
+  pub fn set_saved(&mut self) {
+    if !self.dont_snapshot {
+      self.saved_i = Some(self.viewed_i);
+    } else {
+      self.saved_i = None;
+    }
+  }
+  /// Declare that no known buffer state is saved
+  ///
+  /// Mainly useful for testing, but may be relevant when knowing that file
+  /// was changed on disk.
+  pub fn set_unsaved(&mut self) {
+    self.saved_i = None;
+  }
+
+  /// Get an immutable view into the currently viewed point in history
+  pub fn current(&self) -> &T {
+    &self.snapshots[self.viewed_i].1
+  }
+  /// Get a mutable state to make new changes
+  ///
+  /// - Takes a string describing what is causing this new snapshot. (Should
+  ///   generally be the full command, if not be as clear as possible.)
+  /// - If currently viewing history, will create a revert snapshot at end of
+  ///   history. (In [`HistoryMode::Tree`] a new branch is started instead.)
+  /// - Unless self.dont_snapshot, will create a new snapshot tagged with the
+  ///   given cause for modification.
+  /// - Returns mutable access to the snapshot at the end of history.
+  pub fn current_mut(&mut self,
+    modification_cause: String,
+  ) -> &mut T {
+    self.snapshot(modification_cause);
+    &mut self.snapshots[self.viewed_i].1
+  }
+
+  fn internal_create_snapshot(&mut self, label: String) {
+    // In a linear history the preceding snapshot is always the parent, in a
+    // tree the viewed snapshot is.
+    let parent = match self.mode {
+      HistoryMode::Revert => self.snapshots.len() - 1,
+      HistoryMode::Tree => self.viewed_i,
+    };
+    // Push the current index to end of history with label
+    // (reverts if in history, snapshots if at end of history)
+    self.snapshots.push((label, self.snapshots[self.viewed_i].1.create_snapshot()));
+    self.parents.push(Some(parent));
+    self.children[parent].push(self.snapshots.len() - 1);
+    self.children.push(Vec::new());
+    // Move to end of history
+    self.viewed_i = self.snapshots.len() - 1;
+  }
+  /// Manually add a snapshot
+  ///
+  /// Takes a String as an argument that should describe what causes the
+  /// change seen in the created snapshot relative to the preceding snapshot.
+  ///
+  /// The only case this should be needed is before setting `dont_snapshot` for
+  /// a script execution. If `dont_snapshot` isn't set snapshots are created
+  /// automatically whenever [`Self.current_mut`] is executed.
+  pub fn snapshot(&mut self,
+    modification_cause: String,
+  ) {
+    if self.viewed_i < self.snapshots.len() - 1 {
+      match self.mode {
+        // If we are in the past, create a revert snapshot
+        // This is needed even if snapshots are disabled, to not change history
+        HistoryMode::Revert => {
+          self.internal_create_snapshot(format!(
+            "u{}",
+            self.snapshots.len().saturating_sub(self.viewed_i + 1),
+          ));
+        },
+        // If we are in the past, branch off from there
+        // This is also needed even if snapshots are disabled
+        HistoryMode::Tree => {
+          self.internal_create_snapshot(modification_cause);
+          return;
+        },
+      }
+    }
+    // If snapshots aren't disabled, create one
+    if !self.dont_snapshot {
+      self.internal_create_snapshot(modification_cause);
+    }
+  }
+
+  /// Checks if the last snapshot in history is identical to its parent. If yes
+  /// deletes it.
+  ///
+  /// Intended for use by macros and scripts, as they have to add a snapshot
+  /// even for non-mutating scripts since they don't know if a script will
+  /// modify the buffer. By running this after macro execution the snapshot will
+  /// be deleted if extraneous and left if relevant.
+  pub fn dedup_present(&mut self) {
+    let last = self.snapshots.len() - 1;
+    if let Some(parent) = self.parents[last] {
+      if self.snapshots[last].1 == self.snapshots[parent].1 {
+        self.snapshots.pop();
+        self.parents.pop();
+        self.children.pop();
+        self.children[parent].pop();
+        // Parent is the previous snapshot in a linear history, and the
+        // snapshot we branched off from in a tree.
+        self.viewed_i = parent;
+      }
+    }
+  }
+
+  /// Accessor to view the full list of snapshots
+  ///
+  /// - Entries are in order of creation, the first operation is first in the
+  /// list.
+  /// - The string beside the snapshot describes what caused the state in the
+  ///   snapshot (relative to the preceeding snapshot).
+  pub fn snapshots(&self) -> &Vec<(String, T)> {
+    &self.snapshots
+  }
+  /// Shorthand for `.snapshots().len()`
+  pub fn len(&self) -> usize {
+    self.snapshots.len()
+  }
+  /// Getter for what index was last saved
+  ///
+  /// Returns None if no index is believed to be saved.
+  ///
+  /// Intended to be used to enrich when listing snapshots by marking the one
+  /// considered saved.
+  pub fn saved_i(&self) -> Option<usize> {
+    self.saved_i
+  }
+  /// Getter for currently viewed snapshot index
+  pub fn viewed_i(&self) -> usize {
+    self.viewed_i
+  }
+  /// Setter for currently viewed snapshot index
+  ///
+  /// Returns the modification cause for the now viewed index.
+  ///
+  /// Will return error if given index doesn't hold a snapshot (aka. is too
+  /// big).
+  pub fn set_viewed_i(&mut self, new_i: usize) -> Result<&str> {
+    if new_i < self.len() {
+      self.viewed_i = new_i;
+      Ok(&self.snapshots[self.viewed_i].0)
+    }
+    else {
+      Err(EdError::UndoIndexTooBig{
+        index: new_i,
+        history_len: self.len(),
+        relative_redo_limit: self.len() - self.viewed_i - 1,
+      })
+    }
+  }
+
+  /// Getter for the index of the snapshot the given snapshot was created from
+  ///
+  /// Returns None for the first snapshot and for indices without a snapshot.
+  pub fn parent(&self, i: usize) -> Option<usize> {
+    self.parents.get(i).copied().flatten()
+  }
+  /// Getter for the indices of the snapshots created from the given snapshot
+  ///
+  /// Ordered from oldest to newest.
+  pub fn children(&self, i: usize) -> Vec<usize> {
+    self.children.get(i).cloned().unwrap_or_default()
+  }
+  /// Getter for the last snapshot in every branch of history
+  ///
+  /// Ordered from oldest to newest. In [`HistoryMode::Revert`] there is only
+  /// ever one branch.
+  pub fn branches(&self) -> Vec<usize> {
+    (0 .. self.len()).filter(|i| self.children[*i].is_empty()).collect()
+  }
+  /// Getter for the branch of history currently viewed
+  ///
+  /// Returns the indices of the snapshots from the first snapshot to the viewed
+  /// one, continued by following the newest child until the end of the branch.
+  /// (This is the order in which [`History::undo`] and [`History::redo`] move.)
+  pub fn current_branch(&self) -> Vec<usize> {
+    self.branch_of(self.viewed_i)
+  }
+  /// Getter for the branch of history through the given snapshot
+  ///
+  /// Same as [`History::current_branch`], but through the given index instead
+  /// of the viewed one. Panics if the index doesn't hold a snapshot.
+  pub fn branch_of(&self, i: usize) -> Vec<usize> {
+    // A linear history is a single branch
+    if self.mode == HistoryMode::Revert {
+      assert!(i < self.len(), "No snapshot at index {}", i);
+      return (0 .. self.len()).collect();
+    }
+    let mut branch = vec![i];
+    while let Some(p) = self.parent(*branch.last().unwrap()) {
+      branch.push(p);
+    }
+    branch.reverse();
+    while let Some(c) = self.children[*branch.last().unwrap()].last() {
+      branch.push(*c);
+    }
+    branch
+  }
+
+  /// Move the given number of steps back towards the first snapshot
+  ///
+  /// Returns the modification cause for the now viewed index.
+  ///
+  /// Will return error if there aren't that many snapshots before the viewed
+  /// one.
+  pub fn undo(&mut self, steps: usize) -> Result<&str> {
+    // In a linear history the branch is all of history, in order
+    if self.mode == HistoryMode::Revert {
+      if self.viewed_i < steps {
+        return Err(EdError::UndoIndexNegative{
+          relative_undo_limit: self.viewed_i,
+        });
+      }
+      return self.set_viewed_i(self.viewed_i - steps);
+    }
+    let branch = self.current_branch();
+    let pos = branch.iter().position(|i| *i == self.viewed_i).unwrap_or(0);
+    if pos < steps {
+      return Err(EdError::UndoIndexNegative{relative_undo_limit: pos});
+    }
+    self.set_viewed_i(branch[pos - steps])
+  }
+  /// Move the given number of steps forwards along the current branch
+  ///
+  /// See [`History::current_branch`] for which snapshots are in the branch.
+  /// Returns the modification cause for the now viewed index.
+  ///
+  /// Will return error if there aren't that many snapshots after the viewed
+  /// one.
+  pub fn redo(&mut self, steps: usize) -> Result<&str> {
+    if self.mode == HistoryMode::Revert {
+      return self.set_viewed_i(self.viewed_i + steps);
+    }
+    let branch = self.current_branch();
+    let pos = branch.iter().position(|i| *i == self.viewed_i).unwrap_or(0);
+    match branch.get(pos + steps) {
+      Some(i) => self.set_viewed_i(*i),
+      None => Err(EdError::UndoIndexTooBig{
+        index: pos + steps,
+        history_len: branch.len(),
+        relative_redo_limit: branch.len() - pos - 1,
+      }),
+    }
+  }
+  /// Remove all snapshots before the given snapshot
+  ///
+  /// The given snapshot becomes the first snapshot in history, and only the
+  /// snapshots created from it (directly or indirectly) are kept. In
+  /// [`HistoryMode::Revert`] that is all snapshots after it. The remaining
+  /// snapshots are re-indexed, which the viewed and saved indices follow. (The
+  /// saved index is cleared if the saved snapshot is removed.)
+  ///
+  /// Returns the number of removed snapshots.
+  ///
+  /// Will return error if the given index doesn't hold a snapshot or if the
+  /// viewed snapshot would be removed.
+  pub fn truncate_before(&mut self, i: usize) -> Result<usize> {
+    if i >= self.len() {
+      return Err(EdError::UndoIndexTooBig{
+        index: i,
+        history_len: self.len(),
+        relative_redo_limit: self.len() - self.viewed_i - 1,
+      });
+    }
+    // Parents always have lower indices than their children, so a single pass
+    // finds all descendants.
+    let mut keep = vec![false; self.len()];
+    keep[i] = true;
+    for j in i + 1 .. self.len() {
+      keep[j] = self.parents[j].is_some_and(|p| keep[p]);
+    }
+    if !keep[self.viewed_i] {
+      return Err(EdError::UndoTruncateViewed{index: i});
+    }
+    Ok(self.retain_snapshots(&keep))
+  }
+  /// Remove all snapshots except the viewed one
+  ///
+  /// Intended to free memory in long running sessions. The saved state is kept
+  /// if the viewed snapshot is saved.
+  ///
+  /// Returns the number of removed snapshots.
+  pub fn clear(&mut self) -> usize {
+    let mut keep = vec![false; self.len()];
+    keep[self.viewed_i] = true;
+    self.retain_snapshots(&keep)
+  }
+  // Remove the snapshots not marked to keep, re-indexing all indices. Kept
+  // snapshots whose parent is removed become roots.
+  fn retain_snapshots(&mut self, keep: &[bool]) -> usize {
+    let mut new_indices = Vec::with_capacity(keep.len());
+    let mut kept = 0;
+    for k in keep {
+      new_indices.push(if *k { kept += 1; Some(kept - 1) } else { None });
+    }
+    let old_parents = std::mem::take(&mut self.parents);
+    self.parents = old_parents.into_iter().zip(keep)
+      .filter(|(_, k)| **k)
+      .map(|(p, _)| p.and_then(|p| new_indices[p]))
+      .collect()
+    ;
+    self.children = children_of(&self.parents);
+    let old_snapshots = std::mem::take(&mut self.snapshots);
+    self.snapshots = old_snapshots.into_iter().zip(keep)
+      .filter(|(_, k)| **k)
+      .map(|(s, _)| s)
+      .collect()
+    ;
+    // The viewed snapshot is always kept by the callers
+    self.viewed_i = new_indices[self.viewed_i].unwrap_or(0);
+    self.saved_i = self.saved_i.and_then(|i| new_indices[i]);
+    keep.len() - kept
+  }
+
+  /// Move to the end of another branch of history
+  ///
+  /// Moves the given number of branches away from the current one, in the
+  /// order given by [`History::branches`]. Negative steps moves to older
+  /// branches and positive to newer.
+  ///
+  /// Returns the modification cause for the now viewed index.
+  pub fn switch_branch(&mut self, steps: isize) -> Result<&str> {
+    let branches = self.branches();
+    // The current branch is the one holding the end of current_branch
+    let current = *self.current_branch().last().unwrap();
+    let pos = branches.iter().position(|i| *i == current).unwrap_or(0);
+    let new_pos = pos as isize + steps;
+    if new_pos < 0 || new_pos as usize >= branches.len() {
+      return Err(EdError::UndoBranchTooFar{
+        relative_limit: if steps < 0 { pos } else { branches.len() - pos - 1 },
+      });
+    }
+    self.set_viewed_i(branches[new_pos as usize])
+  }
+}
+
+// Build the index of children for the given parents
+fn children_of(parents: &[Option<usize>]) -> Vec<Vec<usize>> {
+  let mut children = vec![Vec::new(); parents.len()];
+  for (c, p) in parents.iter().enumerate() {
+    if let Some(p) = p { children[*p].push(c); }
+  }
+  children
+}
@@ -0,0 +1,219 @@
+//! Serialization of [`History`] over [`Buffer`], for persisting it to disk.
+//!
+//! Since the snapshots share most of their data we don't serialize each of them
+//! as is. Instead every unique line text is stored once, every unique line
+//! (text + tag, shared through history) is stored once referencing its text,
+//! and the snapshots are stored as lists of indices into the lines.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+use super::{History, HistoryMode, Snapshot, children_of};
+use crate::{Buffer, Line, LineText, PubLine};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerializedHistory<'a> {
+  // Every unique line text in the history
+  texts: Vec<Cow<'a, str>>,
+  // Every unique line in the history, as (tag, index into texts)
+  lines: Vec<(char, usize)>,
+  // Every snapshot, as (label, indices into lines)
+  snapshots: Vec<(Cow<'a, str>, Vec<usize>)>,
+  // The parent of every snapshot, if left out the history is linear
+  #[serde(default)]
+  parents: Vec<Option<usize>>,
+  #[serde(default)]
+  mode: HistoryMode,
+  viewed_i: usize,
+  saved_i: Option<usize>,
+}
+
+impl Serialize for History<Buffer> {
+  fn serialize<S: Serializer>(&self, serializer: S)
+    -> Result<S::Ok, S::Error>
+  {
+    let mut texts = Vec::new();
+    let mut text_indices = HashMap::new();
+    let mut lines = Vec::new();
+    let mut line_indices = HashMap::new();
+    let mut snapshots = Vec::new();
+    for (label, buffer) in &self.snapshots {
+      let mut snapshot = Vec::with_capacity(buffer.len());
+      for line in buffer.iter() {
+        // Lines are identified by their shared tag, so each line is only
+        // stored once no matter how many snapshots it is in.
+        let line_i = *line_indices.entry(line.id()).or_insert_with(|| {
+          // And identical texts are only stored once, even between lines.
+          let text: &str = &line.text;
+          let text_i = *text_indices.entry(text).or_insert_with(|| {
+            texts.push(Cow::Borrowed(text));
+            texts.len() - 1
+          });
+          lines.push((line.tag(), text_i));
+          lines.len() - 1
+        });
+        snapshot.push(line_i);
+      }
+      snapshots.push((Cow::Borrowed(&label[..]), snapshot));
+    }
+    SerializedHistory{
+      texts,
+      lines,
+      snapshots,
+      parents: self.parents.clone(),
+      mode: self.mode,
+      viewed_i: self.viewed_i,
+      saved_i: self.saved_i,
+    }.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for History<Buffer> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D)
+    -> Result<Self, D::Error>
+  {
+    use serde::de::Error;
+    let data = SerializedHistory::deserialize(deserializer)?;
+    let texts = data.texts.into_iter()
+      .map(|t| LineText::new(t).map_err(D::Error::custom))
+      .collect::<Result<Vec<_>, _>>()?
+    ;
+    let lines = data.lines.into_iter()
+      .map(|(tag, text_i)| match texts.get(text_i) {
+        Some(text) => Ok(Line::from(&PubLine{tag, text: text.clone()})),
+        None => Err(D::Error::custom(format!(
+          "line refers to missing text {}",
+          text_i,
+        ))),
+      })
+      .collect::<Result<Vec<_>, _>>()?
+    ;
+    let mut snapshots = Vec::with_capacity(data.snapshots.len());
+    for (label, indices) in data.snapshots {
+      let mut buffer = Buffer::default();
+      for line_i in indices {
+        match lines.get(line_i) {
+          // Snapshotting the line shares the tag between all its instances,
+          // just as when the history was created.
+          Some(line) => buffer.push(line.create_snapshot()),
+          None => return Err(D::Error::custom(format!(
+            "snapshot refers to missing line {}",
+            line_i,
+          ))),
+        }
+      }
+      snapshots.push((label.into_owned(), buffer));
+    }
+    let parents = if data.parents.is_empty() {
+      (0 .. snapshots.len()).map(|i| i.checked_sub(1)).collect()
+    } else {
+      data.parents
+    };
+    // Verify the invariants that History relies upon
+    if snapshots.is_empty() {
+      return Err(D::Error::custom("history has no snapshots"));
+    }
+    if parents.len() != snapshots.len() {
+      return Err(D::Error::custom("history has wrong number of parents"));
+    }
+    // Only the first snapshot lacks a parent, others are created after theirs
+    for (i, p) in parents.iter().enumerate() {
+      let valid = if i == 0 { p.is_none() } else { p.is_some_and(|p| p < i) };
+      if !valid {
+        return Err(D::Error::custom(format!(
+          "snapshot {} has invalid parent",
+          i,
+        )));
+      }
+    }
+    if data.viewed_i >= snapshots.len() {
+      return Err(D::Error::custom("viewed snapshot doesn't exist"));
+    }
+    if data.saved_i.is_some_and(|i| i >= snapshots.len()) {
+      return Err(D::Error::custom("saved snapshot doesn't exist"));
+    }
+    Ok(Self{
+      snapshots,
+      children: children_of(&parents),
+      parents,
+      mode: data.mode,
+      viewed_i: data.viewed_i,
+      saved_i: data.saved_i,
+      dont_snapshot: false,
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn history() -> History<Buffer> {
+    let mut history = History::<Buffer>::new();
+    let buffer = history.current_mut("e file".into());
+    buffer.push("a\n".try_into().unwrap());
+    buffer.push("b\n".try_into().unwrap());
+    buffer.push("a\n".try_into().unwrap());
+    buffer[1].set_tag('x');
+    history.set_saved();
+    history.current_mut("2d".into()).remove(1);
+    history
+  }
+
+  #[test]
+  fn round_trip() {
+    let history = history();
+    let json = serde_json::to_string(&history).unwrap();
+    let loaded: History<Buffer> = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+      loaded.snapshots().iter().map(|(l, _)| &l[..]).collect::<Vec<_>>(),
+      vec!["Before reading in a file (empty)", "e file", "2d"],
+      "Snapshot labels weren't preserved through serialization."
+    );
+    assert_eq!(loaded.viewed_i(), history.viewed_i());
+    assert_eq!(loaded.saved_i(), history.saved_i());
+    assert_eq!(
+      loaded.snapshots()[1].1.get_tagged_lines((1,3)).unwrap()
+        .collect::<Vec<_>>(),
+      vec![('\0', "a\n"), ('x', "b\n"), ('\0', "a\n")],
+      "Snapshot contents weren't preserved through serialization."
+    );
+    // Tags should still be shared through history after loading
+    loaded.current()[0].set_tag('y');
+    assert_eq!(loaded.snapshots()[1].1[0].tag(), 'y');
+    assert_eq!(loaded.snapshots()[1].1[2].tag(), '\0');
+  }
+
+  #[test]
+  fn deduplication() {
+    let json = serde_json::to_value(history()).unwrap();
+    assert_eq!(
+      json["texts"],
+      serde_json::json!(["a\n", "b\n"]),
+      "Identical line texts should only be stored once."
+    );
+    assert_eq!(
+      json["lines"].as_array().unwrap().len(),
+      3,
+      "Lines shared between snapshots should only be stored once."
+    );
+  }
+
+  #[test]
+  fn invalid_index() {
+    let json = r#"{
+      "texts": ["a\n"],
+      "lines": [["\u0000", 1]],
+      "snapshots": [["e", [0]]],
+      "viewed_i": 0,
+      "saved_i": null
+    }"#;
+    assert!(
+      serde_json::from_str::<History<Buffer>>(json).is_err(),
+      "Deserializing history with dangling references should error."
+    );
+  }
+}
@@ -1,5 +1,5 @@
 use crate::{
-  io::IO,
+  io::{IO, ShellContext, LineEnding},
   ui::UILock,
   buffer::iters::LinesIter,
 };
@@ -17,18 +17,21 @@ impl IO for DummyIO {
   fn run_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
   ) -> Result<()> {
     unimplemented!()
   }
   fn run_read_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
   ) -> Result<String> {
     unimplemented!()
   }
   fn run_write_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
     _input: LinesIter,
   ) -> Result<usize> {
     unimplemented!()
@@ -36,6 +39,7 @@ impl IO for DummyIO {
   fn run_transform_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
     _input: LinesIter,
   ) -> Result<String> {
     unimplemented!()
@@ -43,6 +47,7 @@ impl IO for DummyIO {
   fn write_file(&mut self,
     _path: &str,
     _append: bool,
+    _line_ending: LineEnding,
     _data: LinesIter,
   ) -> Result<usize> {
     unimplemented!()
@@ -1,5 +1,5 @@
 use crate::{
-  io::IO,
+  io::{IO, ShellContext, LineEnding},
   ui::UILock,
   buffer::iters::LinesIter,
 };
@@ -32,10 +32,30 @@ pub struct ShellCommand {
 
 /// An [`IO`] implementation intended to simulate filesystem and shell
 /// interactions for testing.
-#[derive(Clone)]
+///
+/// To simulate another program editing a file, change it in `fake_fs` directly.
+#[derive(Clone, Default)]
 pub struct FakeIO {
   pub fake_fs: HashMap<String, String>,
   pub fake_shell: HashMap<ShellCommand, String>,
+  // The contents of each file when last read or written via the IO, to detect
+  // changes made directly in fake_fs
+  fingerprints: HashMap<String, String>,
+  // The commands run, in order, with the context given with them
+  shell_contexts: Vec<(String, ShellContext)>,
+}
+impl FakeIO {
+  /// Create a [`FakeIO`] with the given files and shell commands
+  pub fn new(
+    fake_fs: HashMap<String, String>,
+    fake_shell: HashMap<ShellCommand, String>,
+  ) -> Self {
+    Self{ fake_fs, fake_shell, ..Default::default() }
+  }
+  /// Getter for the commands run, in order, with the context given with them
+  pub fn shell_contexts(&self) -> &[(String, ShellContext)] {
+    &self.shell_contexts
+  }
 }
 
 impl IO for FakeIO {
@@ -44,7 +64,9 @@ impl IO for FakeIO {
   fn run_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
   ) -> Result<()> {
+    self.shell_contexts.push((command.clone(), context.clone()));
     if self.fake_shell.contains_key(
       &ShellCommand{command, input: String::new()}
     ) {
@@ -59,7 +81,9 @@ impl IO for FakeIO {
   fn run_read_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
   ) -> Result<String> {
+    self.shell_contexts.push((command.clone(), context.clone()));
     match self.fake_shell.get(
       &ShellCommand{command, input: String::new()}
     ) {
@@ -73,8 +97,10 @@ impl IO for FakeIO {
   fn run_write_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
     input: LinesIter,
   ) -> Result<usize> {
+    self.shell_contexts.push((command.clone(), context.clone()));
     let input = input.fold(String::new(), |mut s, x| {s.push_str(x); s});
     let inputlen = input.len();
     match self.fake_shell.get(
@@ -90,8 +116,10 @@ impl IO for FakeIO {
   fn run_transform_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
     input: LinesIter,
   ) -> Result<String> {
+    self.shell_contexts.push((command.clone(), context.clone()));
     let input = input.fold(String::new(), |mut s, x| {s.push_str(x); s});
     match self.fake_shell.get(
       &ShellCommand{command, input}
@@ -104,6 +132,7 @@ impl IO for FakeIO {
   fn write_file(&mut self,
     path: &str,
     append: bool,
+    line_ending: LineEnding,
     data: LinesIter,
   ) -> Result<usize> {
     let base_data = if append {
@@ -114,8 +143,15 @@ impl IO for FakeIO {
     } else {
       String::new()
     };
-    let data = data.fold(base_data, |mut s, x|{s.push_str(x); s});
+    let data = data.fold(base_data, |mut s, x|{
+      s.push_str(&line_ending.apply(x));
+      s
+    });
     let datalen = data.len();
+    // Appending to a changed file shouldn't hide that it was changed
+    if !append || !self.file_changed(path)? {
+      self.fingerprints.insert(path.to_owned(), data.clone());
+    }
     self.fake_fs.insert(path.to_owned(), data);
     Ok(datalen)
   }
@@ -126,7 +162,10 @@ impl IO for FakeIO {
     must_exist: bool,
   ) -> Result<String> {
     match self.fake_fs.get(path) {
-      Some(x) => Ok(x.to_owned()),
+      Some(x) => {
+        self.fingerprints.insert(path.to_owned(), x.to_owned());
+        Ok(x.to_owned())
+      },
       None => if must_exist {
         Err(FakeIOError::NotFound.into())
       } else {
@@ -134,4 +173,27 @@ impl IO for FakeIO {
       },
     }
   }
+  /// Compares the file in `fake_fs` with its contents when last read or
+  /// written.
+  fn file_changed(&mut self,
+    path: &str,
+  ) -> Result<bool> {
+    match (self.fingerprints.get(path), self.fake_fs.get(path)) {
+      (Some(fingerprint), Some(current)) => Ok(fingerprint != current),
+      _ => Ok(false),
+    }
+  }
+  /// Stores the history in `fake_fs` at [`super::history_path`].
+  fn write_history(&mut self,
+    path: &str,
+    data: &str,
+  ) -> Result<()> {
+    self.fake_fs.insert(super::history_path(path), data.to_owned());
+    Ok(())
+  }
+  fn read_history(&mut self,
+    path: &str,
+  ) -> Result<Option<String>> {
+    Ok(self.fake_fs.get(&super::history_path(path)).cloned())
+  }
 }
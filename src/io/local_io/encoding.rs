@@ -0,0 +1,97 @@
+// Conversion between the bytes of files/commands and the text in the buffer
+
+use std::borrow::Cow;
+use super::LocalIOError;
+
+// Bytes that aren't valid UTF-8 are all in 0x80..=0xFF and are escaped as the
+// char at this offset plus the byte, giving U+F780..=U+F7FF (private use).
+const ESCAPE_BASE: u32 = 0xF700;
+
+/// How [`LocalIO`](super::LocalIO) converts between bytes and text
+///
+/// Applied the same way to all data read from and written to files and
+/// commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+  /// Require valid UTF-8, erroring with [`LocalIOError::BadUtf8`] otherwise.
+  Utf8,
+  /// Decode as UTF-8, but preserve any bytes that aren't valid UTF-8.
+  ///
+  /// Each such byte is represented by a char in U+F780 to U+F7FF (offset from
+  /// U+F700 by the byte's value), which is written back as the original byte.
+  /// Chars in that range that were valid UTF-8 in the data are escaped byte by
+  /// byte, so they survive the round trip too.
+  EscapedBytes,
+}
+
+fn escape(byte: u8) -> char {
+  // Only used for bytes >= 0x80, which give chars within the private use area
+  char::from_u32(ESCAPE_BASE + byte as u32).unwrap()
+}
+fn unescape(c: char) -> Option<u8> {
+  match c as u32 {
+    x @ 0xF780 ..= 0xF7FF => Some((x - ESCAPE_BASE) as u8),
+    _ => None,
+  }
+}
+// Pushes valid text, escaping any chars that would be taken as escapes
+fn push_escaped(out: &mut String, text: &str) {
+  for c in text.chars() {
+    if unescape(c).is_some() {
+      let mut buf = [0; 4];
+      for byte in c.encode_utf8(&mut buf).bytes() { out.push(escape(byte)); }
+    } else {
+      out.push(c);
+    }
+  }
+}
+
+impl Encoding {
+  pub(super) fn decode(self, bytes: Vec<u8>) -> Result<String, LocalIOError> {
+    match self {
+      Self::Utf8 => String::from_utf8(bytes).map_err(LocalIOError::BadUtf8),
+      Self::EscapedBytes => {
+        let mut out = String::with_capacity(bytes.len());
+        let mut rest = &bytes[..];
+        loop { match std::str::from_utf8(rest) {
+          Ok(text) => {
+            push_escaped(&mut out, text);
+            break;
+          },
+          Err(e) => {
+            let valid = e.valid_up_to();
+            // Safe, since from_utf8 verified the bytes up until valid
+            push_escaped(
+              &mut out,
+              std::str::from_utf8(&rest[..valid]).unwrap(),
+            );
+            // No error_len means the data ends with an incomplete char
+            let invalid = e.error_len().unwrap_or(rest.len() - valid);
+            for byte in &rest[valid .. valid + invalid] {
+              out.push(escape(*byte));
+            }
+            rest = &rest[valid + invalid ..];
+          },
+        }}
+        Ok(out)
+      },
+    }
+  }
+  pub(super) fn encode(self, text: Cow<'_, str>) -> Cow<'_, [u8]> {
+    if self == Self::Utf8 || !text.chars().any(|c| unescape(c).is_some()) {
+      return match text {
+        Cow::Borrowed(x) => Cow::Borrowed(x.as_bytes()),
+        Cow::Owned(x) => Cow::Owned(x.into_bytes()),
+      };
+    }
+    let mut out = Vec::with_capacity(text.len());
+    for c in text.chars() { match unescape(c) {
+      Some(byte) => out.push(byte),
+      None => {
+        let mut buf = [0; 4];
+        out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+      },
+    }}
+    Cow::Owned(out)
+  }
+}
@@ -25,6 +25,10 @@ pub enum LocalIOError {
   ChildKilledBySignal,
   /// Error occured in the child thread handling piping
   ChildPipingError,
+  /// The shell command ran longer than the configured timeout, so it was killed
+  ChildTimedOut(std::time::Duration),
+  /// The UI cancelled the shell command, so it was killed
+  ChildCancelled,
    /// Failed to convert data read from file or command into UTF8
   BadUtf8(std::string::FromUtf8Error),
 }
@@ -83,6 +87,13 @@ impl std::fmt::Display for LocalIOError {
       Self::ChildPipingError => { write!(f,
         "Error while piping data.",
       )},
+      Self::ChildTimedOut(timeout) => { write!(f,
+        "Shell process was killed after timing out ({:?}).",
+        timeout,
+      )},
+      Self::ChildCancelled => { write!(f,
+        "Shell process was cancelled.",
+      )},
       Self::BadUtf8(e) => { write!(f,
         "Bad UTF-8 in read data.\nUnderlying error: {}",
         e,
@@ -100,6 +111,8 @@ impl std::cmp::PartialEq for LocalIOError {
       (ChildReturnedError(a),ChildReturnedError(b)) => a == b,
       (ChildKilledBySignal,ChildKilledBySignal) => true,
       (ChildPipingError,ChildPipingError) => true,
+      (ChildTimedOut(a),ChildTimedOut(b)) => a == b,
+      (ChildCancelled,ChildCancelled) => true,
       (BadUtf8(a),BadUtf8(b)) => a == b,
       // std::io::Error doesn't implement PartialEq, so we check the ErrorKind
       (FileIOFailed{path: a, error: b},FileIOFailed{path: c, error: d}) =>
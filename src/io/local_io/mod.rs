@@ -3,10 +3,18 @@
 // solutions that I could put behind a feature if I get some API design help.
 
 use std::process::{
+  Child,
   Command,
+  ExitStatus,
   Stdio,
 };
+use std::time::{
+  Duration,
+  Instant,
+};
 use crate::IO;
+use super::ShellContext;
+use super::LineEnding;
 use super::LinesIter;
 use crate::UILock;
 use super::Result;
@@ -15,41 +23,282 @@ use super::Result;
 mod error;
 pub use error::LocalIOError;
 
+mod encoding;
+pub use encoding::Encoding;
+
 #[cfg(all(feature = "test_local_io", test))]
 mod test;
 
-fn spawn_transfer<'a, I, O>(
+fn spawn_transfer<I, S, O>(
   i: I,
   mut o: O,
-) -> std::thread::JoinHandle<usize> where
-  I: Iterator<Item = &'a str>,
+) -> std::thread::JoinHandle<std::io::Result<usize>> where
+  I: Iterator<Item = S>,
+  S: AsRef<[u8]>,
   O: std::io::Write + std::marker::Send + 'static,
 {
-  let aggregated_input = i.fold(String::new(),|mut s, a| {s.push_str(a); s});
+  let aggregated_input = i.fold(Vec::new(),|mut s, a| {
+    s.extend_from_slice(a.as_ref());
+    s
+  });
+  std::thread::spawn(move || {
+    // Fails if the child exits (or is killed) without reading all input
+    o.write_all(&aggregated_input)?;
+    Ok(aggregated_input.len())
+  })
+}
+
+// Collects all of the output in a separate thread, so the child can't block on
+// a full pipe while we wait for it
+fn spawn_collect<R>(
+  mut r: R,
+) -> std::thread::JoinHandle<std::io::Result<Vec<u8>>> where
+  R: std::io::Read + std::marker::Send + 'static,
+{
   std::thread::spawn(move || {
-    let inputlen = aggregated_input.len();
-    o.write_all(aggregated_input.as_bytes()).expect("Pipe forwarding failed.");
-    inputlen
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+    Ok(data)
   })
 }
 
+// How often a child is checked on when it may need to be killed
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Waits for the child to exit, killing it if it times out or the UI cancels it
+//
+// If own_group is set the child's whole process group is killed, so commands
+// it started can't keep running (or keep our pipes open).
+fn wait_for_child(
+  child: &mut Child,
+  ui: &UILock,
+  timeout: Option<Duration>,
+  own_group: bool,
+) -> std::result::Result<ExitStatus, LocalIOError> {
+  if timeout.is_none() && !ui.is_cancellable() {
+    return child.wait().map_err(LocalIOError::ChildFailedToStart);
+  }
+  let start = Instant::now();
+  loop {
+    if let Some(status) = child.try_wait()
+      .map_err(LocalIOError::ChildFailedToStart)?
+    {
+      return Ok(status);
+    }
+    let error = if ui.is_cancelled() {
+      LocalIOError::ChildCancelled
+    } else {
+      match timeout {
+        Some(t) if start.elapsed() >= t => LocalIOError::ChildTimedOut(t),
+        _ => {
+          std::thread::sleep(POLL_INTERVAL);
+          continue;
+        },
+      }
+    };
+    kill_child(child, own_group);
+    return Err(error);
+  }
+}
+
+fn kill_child(child: &mut Child, own_group: bool) {
+  #[cfg(unix)]
+  if own_group {
+    use nix::{sys::signal::{killpg, Signal}, unistd::Pid};
+    // The child (the group's leader) isn't reaped yet, so its id can't have
+    // been reused
+    let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+  }
+  #[cfg(not(unix))]
+  let _ = own_group;
+  // Already dead if it was in the group, but also reaps it
+  let _ = child.kill();
+  let _ = child.wait();
+}
+
+// Converts all "\r\n" line endings into '\n', since the buffer only uses '\n'
+fn normalize_line_endings(text: String) -> String {
+  if text.contains("\r\n") { text.replace("\r\n", "\n") } else { text }
+}
+
+// Gives the terminal to the child's process group while held
+//
+// Needed by children in their own group that are given the terminal as stdin,
+// since they'd be stopped when reading it otherwise. Also makes Ctrl-C go to
+// the child rather than us, as if it was in our group.
+#[cfg(unix)]
+struct Foreground;
+#[cfg(unix)]
+impl Foreground {
+  // Returns None if not in its own group or stdin isn't a terminal
+  fn give(child: &Child, own_group: bool) -> Option<Self> {
+    use std::io::IsTerminal;
+    use nix::{sys::signal::{killpg, Signal}, unistd::{tcsetpgrp, Pid}};
+    if !own_group || !std::io::stdin().is_terminal() { return None; }
+    let group = Pid::from_raw(child.id() as i32);
+    tcsetpgrp(std::io::stdin(), group).ok()?;
+    // The child is stopped if it read the terminal before it got it, so
+    // continue it in case
+    let _ = killpg(group, Signal::SIGCONT);
+    Some(Self)
+  }
+}
+#[cfg(unix)]
+impl Drop for Foreground {
+  fn drop(&mut self) {
+    use nix::{
+      sys::signal::{SigSet, SigmaskHow, Signal},
+      unistd::{tcsetpgrp, getpgrp},
+    };
+    // Taking the terminal back from the background raises SIGTTOU, which would
+    // stop us unless blocked
+    let mut ttou = SigSet::empty();
+    ttou.add(Signal::SIGTTOU);
+    let previous = ttou.thread_swap_mask(SigmaskHow::SIG_BLOCK);
+    let _ = tcsetpgrp(std::io::stdin(), getpgrp());
+    if let Ok(previous) = previous { let _ = previous.thread_set_mask(); }
+  }
+}
+
+/// How [`LocalIO`] saves files, given to [`LocalIO::with_save_options`]
+///
+/// Only applies when overwriting files, appending is always done in place.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SaveOptions {
+  /// Write into a temporary file beside the target, sync it to disk and then
+  /// rename it over the target. This way a failure while writing can't leave
+  /// the file half written. The target's permissions are kept, and symlinks
+  /// are followed so the file they point to is replaced.
+  pub atomic: bool,
+  /// Copy the file to the same path with a `~` appended before overwriting it.
+  pub backup: bool,
+}
+
 /// Filesystem and process tree local [`IO`] implementation.
+///
+/// Files are written with the line ending the editor detected when reading
+/// them, so files with "\r\n" line endings keep them. Output from commands has
+/// its "\r\n" line endings converted into '\n'.
+///
+/// Data is converted to and from bytes as configured by `encoding`.
 #[non_exhaustive]
 pub struct LocalIO {
+  /// How data read and written is converted to and from bytes.
+  pub encoding: Encoding,
+  /// Set to write this line ending into all files, instead of the one the
+  /// editor detected when reading the file.
+  pub file_line_ending: Option<LineEnding>,
+  /// The line ending written into the input of shell commands.
+  pub command_line_ending: LineEnding,
+  /// Kill shell commands that run for longer than this, if set.
+  ///
+  /// Commands can also be cancelled by the UI, see
+  /// [`UILock::with_cancel_flag`]. On Unix the command's whole process group is
+  /// killed, elsewhere only the shell. Commands for `!`, `r !` and `e !` are
+  /// given the terminal while running (if stdin is one), so Ctrl-C goes to
+  /// them rather than to the UI.
+  pub command_timeout: Option<Duration>,
+  // How to save files
+  save_options: SaveOptions,
+  // The modification time and size of each file when last read or written
+  fingerprints: std::collections::HashMap<String, Fingerprint>,
+}
+
+// Identifies a version of a file, to detect if it changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fingerprint {
+  modified: Option<std::time::SystemTime>,
+  len: u64,
+}
+impl Fingerprint {
+  // Returns None if there is no file at the path
+  fn of(path: &str) -> std::io::Result<Option<Self>> {
+    match std::fs::metadata(path) {
+      Ok(metadata) => Ok(Some(Self{
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+      })),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
 }
 impl LocalIO {
   /// Construct LocalIO instance
   ///
-  /// Currently there are no internal members in [`LocalIO`], but for stability
-  /// into the future I'd still recommend using this method in case a need for
-  /// state is found.
+  /// Defaults to requiring UTF-8, writing files with the line ending given by
+  /// the editor and writing '\n' line endings into commands.
   pub fn new() -> Self {
-    Self{}
+    Self{
+      encoding: Encoding::Utf8,
+      file_line_ending: None,
+      command_line_ending: LineEnding::LF,
+      command_timeout: None,
+      save_options: SaveOptions::default(),
+      fingerprints: std::collections::HashMap::new(),
+    }
+  }
+  /// Construct LocalIO instance saving files as configured
+  ///
+  /// Otherwise the same defaults as [`LocalIO::new`].
+  pub fn with_save_options(save_options: SaveOptions) -> Self {
+    Self{ save_options, ..Self::new() }
+  }
+  // Saves the fingerprint of the file at path, to detect later changes
+  fn record_fingerprint(&mut self, path: &str) -> std::io::Result<()> {
+    match Fingerprint::of(path)? {
+      Some(fingerprint) => self.fingerprints.insert(path.to_owned(), fingerprint),
+      None => self.fingerprints.remove(path),
+    };
+    Ok(())
+  }
+  // If a command should get its own process group
+  //
+  // Only done if it may need to be killed, to otherwise leave the process
+  // handling as it was. Commands given the terminal as stdin must then also be
+  // given the terminal, see Foreground.
+  fn own_group(&self, ui: &UILock) -> bool {
+    cfg!(unix) && (self.command_timeout.is_some() || ui.is_cancellable())
   }
-  fn write_internal<'a>(
+  // Creates the command, in its own process group if given own_group
+  fn shell_command(
+    command: String,
+    context: &ShellContext,
+    own_group: bool,
+  ) -> Command {
+    let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
+    let mut cmd = Command::new(shell);
+    cmd.arg("-c")
+      .arg(command)
+      .envs(context.env_vars())
+    ;
+    #[cfg(unix)]
+    if own_group {
+      use std::os::unix::process::CommandExt;
+      cmd.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = own_group;
+    cmd
+  }
+  // Converts lines from the buffer into the bytes to write
+  fn encode_lines<'a>(
+    &self,
+    line_ending: LineEnding,
+    data: LinesIter<'a>,
+  ) -> impl Iterator<Item = std::borrow::Cow<'a, [u8]>> {
+    let encoding = self.encoding;
+    data.map(move |line| encoding.encode(line_ending.apply(line)))
+  }
+  // Converts read bytes into text for the buffer
+  fn decode(&self, data: Vec<u8>) -> Result<String> {
+    let text = self.encoding.decode(data)?;
+    Ok(normalize_line_endings(text))
+  }
+  fn write_internal<S: AsRef<[u8]>>(
     path: &str,
     append: bool,
-    data: impl Iterator<Item = &'a str>,
+    data: impl Iterator<Item = S>,
   ) -> std::io::Result<usize> {
     use std::io::Write;
     let mut file = std::fs::OpenOptions::new()
@@ -61,13 +310,105 @@ impl LocalIO {
     ?;
     let mut written = 0;
     for line in data {
+      let line = line.as_ref();
       written += line.len();
-      file.write_all(line.as_bytes())?;
+      file.write_all(line)?;
     }
     file.flush()?;
     Ok(written)
   }
 }
+// Overwrites the file at path with the data, as configured by the options
+// Resolves all symlinks in the path, also if the last one is dangling
+fn resolve_links(path: &str) -> std::io::Result<std::path::PathBuf> {
+  match std::fs::canonicalize(path) {
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+    res => return res,
+  }
+  // Follow the links to the missing file by hand, as canonicalize requires it
+  // to exist. (Loops make canonicalize error otherwise, so this terminates.)
+  let mut target = std::path::PathBuf::from(path);
+  while let Ok(link) = std::fs::read_link(&target) {
+    // Relative links are relative to the directory holding the link
+    target = match target.parent() {
+      Some(dir) => dir.join(link),
+      None => link,
+    };
+  }
+  Ok(target)
+}
+
+fn save_internal<S: AsRef<[u8]>>(
+  path: &str,
+  options: SaveOptions,
+  data: impl Iterator<Item = S>,
+) -> std::io::Result<usize> {
+  use std::io::Write;
+  if options.backup {
+    match std::fs::copy(path, format!("{}~", path)) {
+      Ok(_) => (),
+      // Nothing to back up if the file doesn't exist yet
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+      Err(e) => return Err(e),
+    }
+  }
+  if !options.atomic { return LocalIO::write_internal(path, false, data); }
+  // Follow symlinks, so we replace the file rather than the link
+  let target = resolve_links(path)?;
+  // The temporary file must be in the same directory for rename to be atomic
+  let name = target.file_name().unwrap_or_default().to_string_lossy();
+  let temp = target.with_file_name(
+    format!(".{}.{}.ed-tmp", name, std::process::id())
+  );
+  let permissions = match std::fs::metadata(&target) {
+    Ok(metadata) => Some(metadata.permissions()),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+    Err(e) => return Err(e),
+  };
+  let write_temp = || -> std::io::Result<usize> {
+    // Never write into a file left behind by an earlier save, its permissions
+    // may be wider than the target's
+    let _ = std::fs::remove_file(&temp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // Create it with the target's permissions, so the data is never readable
+    // by more users than the target's is
+    #[cfg(unix)]
+    if let Some(permissions) = &permissions {
+      use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+      options.mode(permissions.mode());
+    }
+    let mut file = options.open(&temp)?;
+    // (Also sets the bits the umask removed at creation)
+    if let Some(permissions) = permissions {
+      file.set_permissions(permissions)?;
+    }
+    let mut written = 0;
+    for line in data {
+      let line = line.as_ref();
+      written += line.len();
+      file.write_all(line)?;
+    }
+    file.flush()?;
+    file.sync_all()?;
+    std::fs::rename(&temp, &target)?;
+    Ok(written)
+  };
+  let written = write_temp().inspect_err(|_| {
+    // Clean up after ourselves, the original error is more relevant
+    let _ = std::fs::remove_file(&temp);
+  })?;
+  // Sync the directory too, so the rename persists. Not supported everywhere,
+  // so errors are ignored.
+  if let Some(dir) = target.parent() {
+    let dir = if dir.as_os_str().is_empty() { std::path::Path::new(".") }
+      else { dir }
+    ;
+    if let Ok(dir) = std::fs::File::open(dir) { let _ = dir.sync_all(); }
+  }
+  Ok(written)
+}
+
 impl Default for LocalIO {
   fn default() -> Self {
     Self::new()
@@ -76,19 +417,19 @@ impl Default for LocalIO {
 
 impl IO for LocalIO {
   fn run_command(&mut self,
-    _ui: &mut UILock,
+    ui: &mut UILock,
     command: String,
+    context: &ShellContext,
   ) -> Result<()> {
-    let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
+    let own_group = self.own_group(ui);
     // Create and run child process, passing through all io
-    let res = Command::new(shell)
-      .arg("-c")
-      .arg(command)
+    let mut child = Self::shell_command(command, context, own_group)
       .spawn() // When spawn io defaults to inherited
       .map_err(LocalIOError::ChildCreationFailed)?
-      .wait()
-      .map_err(LocalIOError::ChildFailedToStart)?
     ;
+    #[cfg(unix)]
+    let _foreground = Foreground::give(&child, own_group);
+    let res = wait_for_child(&mut child, ui, self.command_timeout, own_group)?;
     if !(res.success()) {
       return Err(LocalIOError::child_return_res(res.code()).into());
     }
@@ -96,56 +437,63 @@ impl IO for LocalIO {
   }
 
   fn run_read_command(&mut self,
-    _ui: &mut UILock,
+    ui: &mut UILock,
     command: String,
+    context: &ShellContext,
   ) -> Result<String> {
-    let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
+    let own_group = self.own_group(ui);
     // Create child process
-    let child = Command::new(shell)
-      .arg("-c")
-      .arg(command)
+    let mut child = Self::shell_command(command, context, own_group)
       .stdout(Stdio::piped())
       .spawn()
       .map_err(LocalIOError::ChildCreationFailed)?
     ;
+    #[cfg(unix)]
+    let _foreground = Foreground::give(&child, own_group);
+    let o = spawn_collect(child.stdout.take().unwrap());
     // Blocks until child has finished running
-    let res = child.wait_with_output()
-      .map_err(LocalIOError::ChildFailedToStart)
-    ?;
-    if !(res.status.success()) {
-      return Err(LocalIOError::child_return_res(res.status.code()).into());
-    }
-    let output = String::from_utf8(res.stdout)
-      .map_err(LocalIOError::BadUtf8)?
+    let res = wait_for_child(&mut child, ui, self.command_timeout, own_group);
+    // Wait for the other child thread before triggering early returns with ?
+    // (If the child was killed the pipe is closed, so the thread finishes)
+    let output = o.join();
+    let res = res?;
+    let output = output
+      .map_err(|_|LocalIOError::ChildPipingError)?
+      .map_err(|_|LocalIOError::ChildPipingError)?
     ;
-    Ok(output)
+    if !(res.success()) {
+      return Err(LocalIOError::child_return_res(res.code()).into());
+    }
+    self.decode(output)
   }
 
   fn run_write_command(&mut self,
-    _ui: &mut UILock,
+    ui: &mut UILock,
     command: String,
+    context: &ShellContext,
     input: LinesIter,
   ) -> Result<usize> {
-    let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
+    let own_group = self.own_group(ui);
     // Create child process
-    let mut child = Command::new(shell)
-      .arg("-c")
-      .arg(command)
+    let mut child = Self::shell_command(command, context, own_group)
       .stdin(Stdio::piped())
       .spawn()
       .map_err(LocalIOError::ChildCreationFailed)?
     ;
     let i = spawn_transfer(
-      input,
+      self.encode_lines(self.command_line_ending, input),
       child.stdin.take().unwrap(),
     );
     // Blocks until child has finished running
-    let res = child.wait()
-      .map_err(LocalIOError::ChildFailedToStart)
-    ;
+    let res = wait_for_child(&mut child, ui, self.command_timeout, own_group);
     // Wait for the other child thread before triggering early returns with ?
-    let transfer_res = i.join().map_err(|_|LocalIOError::ChildPipingError)?;
+    // (If the child was killed the pipe is closed, so the thread finishes)
+    let transfer_res = i.join();
     let res = res?;
+    let transfer_res = transfer_res
+      .map_err(|_|LocalIOError::ChildPipingError)?
+      .map_err(|_|LocalIOError::ChildPipingError)?
+    ;
     if !(res.success()) {
       return Err(LocalIOError::child_return_res(res.code()).into());
     }
@@ -153,54 +501,78 @@ impl IO for LocalIO {
   }
 
   fn run_transform_command(&mut self,
-    _ui: &mut UILock,
+    ui: &mut UILock,
     command: String,
+    context: &ShellContext,
     input: LinesIter,
   ) -> Result<String> {
-    let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
+    let own_group = self.own_group(ui);
     // Create child process
-    let mut child = Command::new(shell)
-      .arg("-c")
-      .arg(command)
+    let mut child = Self::shell_command(command, context, own_group)
       .stdin(Stdio::piped())
       .stdout(Stdio::piped())
       .spawn()
       .map_err(LocalIOError::ChildCreationFailed)?
     ;
     let i = spawn_transfer(
-      input,
+      self.encode_lines(self.command_line_ending, input),
       child.stdin.take().unwrap(),
     );
+    let o = spawn_collect(child.stdout.take().unwrap());
     // Blocks until child has finished running
-    let res = child.wait_with_output()
-      .map_err(LocalIOError::ChildFailedToStart)
-    ;
-    // Wait for the other child thread before triggering early returns with ?
-    let _transfer_res = i.join().map_err(|_|LocalIOError::ChildPipingError)?;
+    let res = wait_for_child(&mut child, ui, self.command_timeout, own_group);
+    // Wait for the other child threads before triggering early returns with ?
+    // (If the child was killed the pipes are closed, so the threads finish)
+    let transfer_res = i.join();
+    let output = o.join();
     let res = res?;
-    if !(res.status.success()) {
-      return Err(LocalIOError::child_return_res(res.status.code()).into());
+    let _transfer_res = transfer_res
+      .map_err(|_|LocalIOError::ChildPipingError)?
+      .map_err(|_|LocalIOError::ChildPipingError)?
+    ;
+    let output = output
+      .map_err(|_|LocalIOError::ChildPipingError)?
+      .map_err(|_|LocalIOError::ChildPipingError)?
+    ;
+    if !(res.success()) {
+      return Err(LocalIOError::child_return_res(res.code()).into());
     }
-    let output = String::from_utf8_lossy(&res.stdout).into_owned();
-    Ok(output)
+    self.decode(output)
   }
 
   fn write_file(&mut self,
     path: &str,
     append: bool,
+    line_ending: LineEnding,
     data: LinesIter,
   ) -> Result<usize> {
-    Self::write_internal(path, append, data)
-      .map_err(|e| LocalIOError::file_error(path, e).into())
+    // Appending to a changed file shouldn't hide that it was changed
+    let record = !append || !self.file_changed(path)?;
+    let line_ending = self.file_line_ending.unwrap_or(line_ending);
+    let data = self.encode_lines(line_ending, data);
+    let written = if append { Self::write_internal(path, append, data) }
+      else { save_internal(path, self.save_options, data) }
+      .map_err(|e| LocalIOError::file_error(path, e))?
+    ;
+    if record {
+      self.record_fingerprint(path)
+        .map_err(|e| LocalIOError::file_error(path, e))?;
+    }
+    Ok(written)
   }
   fn read_file(&mut self,
     path: &str,
     must_exist: bool,
   ) -> Result<String> {
-    match std::fs::read_to_string(path)
+    match std::fs::read(path)
       .map_err(|e| LocalIOError::file_error(path, e))
     {
-      Ok(data) => Ok(data),
+      Ok(data) => {
+        self.record_fingerprint(path)
+          .map_err(|e| LocalIOError::file_error(path, e))?;
+        // The line endings are left as is, for the editor to detect
+        Ok(self.encoding.decode(data)?)
+      },
       Err(e) => match e {
         LocalIOError::FileNotFound{..} => {
           if must_exist { Err(e.into()) } else { Ok(String::new()) }
@@ -209,4 +581,37 @@ impl IO for LocalIO {
       },
     }
   }
+  fn file_changed(&mut self,
+    path: &str,
+  ) -> Result<bool> {
+    let recorded = match self.fingerprints.get(path) {
+      Some(x) => *x,
+      None => return Ok(false),
+    };
+    match Fingerprint::of(path).map_err(|e| LocalIOError::file_error(path, e))? {
+      Some(current) => Ok(current != recorded),
+      None => Ok(false),
+    }
+  }
+  fn write_history(&mut self,
+    path: &str,
+    data: &str,
+  ) -> Result<()> {
+    let path = super::history_path(path);
+    Self::write_internal(&path, false, std::iter::once(data))
+      .map_err(|e| LocalIOError::file_error(&path, e))?;
+    Ok(())
+  }
+  fn read_history(&mut self,
+    path: &str,
+  ) -> Result<Option<String>> {
+    let path = super::history_path(path);
+    match std::fs::read_to_string(&path)
+      .map_err(|e| LocalIOError::file_error(&path, e))
+    {
+      Ok(data) => Ok(Some(data)),
+      Err(LocalIOError::FileNotFound{..}) => Ok(None),
+      Err(e) => Err(e.into()),
+    }
+  }
 }
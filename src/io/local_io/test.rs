@@ -21,6 +21,7 @@ fn test_file_io() {
   io.write_file(
     path,
     false, // don't append
+    LineEnding::LF,
     Box::new("1\n2\n".split_inclusive('\n')).into(),
   ).unwrap();
   let read = std::fs::read_to_string(path).unwrap();
@@ -34,6 +35,7 @@ fn test_file_io() {
   io.write_file(
     path,
     false, // don't append
+    LineEnding::LF,
     Box::new("1\n2\n".split_inclusive('\n')).into(),
   ).unwrap();
   let read = std::fs::read_to_string(path).unwrap();
@@ -47,6 +49,7 @@ fn test_file_io() {
   io.write_file(
     path,
     true, // Append
+    LineEnding::LF,
     Box::new("1\n2\n".split_inclusive('\n')).into(),
   ).unwrap();
   let read = std::fs::read_to_string(path).unwrap();
@@ -87,6 +90,7 @@ fn test_command_io() {
   io.run_command(
     &mut mock_ui_lock,
     "echo \"hurr\ndurr\" > io_command_test_file".to_owned(),
+    &ShellContext::default(),
   ).unwrap();
   let data = io.read_file("io_command_test_file", true).unwrap();
   assert_eq!(
@@ -98,6 +102,7 @@ fn test_command_io() {
   let res = io.run_command(
     &mut mock_ui_lock,
     "false".to_owned(),
+    &ShellContext::default(),
   );
   match res {
     Ok(_) => panic!("No error returned when child process failed to run."),
@@ -118,6 +123,7 @@ fn test_command_io() {
   let data = io.run_read_command(
     &mut mock_ui_lock,
     "echo \"hurr\ndurr\"".to_owned(),
+    &ShellContext::default(),
   ).unwrap();
   assert_eq!(
     &data,
@@ -129,6 +135,7 @@ fn test_command_io() {
   let written = io.run_write_command(
     &mut mock_ui_lock,
     "cat > io_command_test_file".to_owned(),
+    &ShellContext::default(),
     Box::new("hurr\ndurr\ndunn\n".split_inclusive('\n')).into(),
   ).unwrap();
   assert_eq!(
@@ -148,6 +155,7 @@ fn test_command_io() {
   let output = io.run_transform_command(
     &mut mock_ui_lock,
     "sort -n".to_owned(),
+    &ShellContext::default(),
     Box::new("4\n5\n8\n1\n3\n2\n6\n0\n9\n7\n10\n".split_inclusive('\n')).into(),
   ).unwrap();
   assert_eq!(
@@ -156,3 +164,412 @@ fn test_command_io() {
     "Transform command running did not have expected effect."
   );
 } 
+
+#[test]
+fn test_crlf_io() {
+  let mut io = LocalIO::new();
+  let path = "io_crlf_test_file";
+  std::fs::write(path, "1\r\n2\r\n").unwrap();
+  // Reading leaves the line endings for the editor to detect
+  let read = io.read_file(path, true).unwrap();
+  assert_eq!(&read, "1\r\n2\r\n", "Read changed the line endings.");
+  io.write_file(
+    path,
+    false, // don't append
+    LineEnding::CRLF,
+    Box::new("1\n3\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert_eq!(
+    &std::fs::read_to_string(path).unwrap(),
+    "1\r\n3\r\n",
+    "Write didn't use the given line ending."
+  );
+  // Forcing a line ending overrides the given one
+  io.file_line_ending = Some(LineEnding::LF);
+  io.write_file(
+    path,
+    false, // don't append
+    LineEnding::CRLF,
+    Box::new("1\n3\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert_eq!(
+    &std::fs::read_to_string(path).unwrap(),
+    "1\n3\n",
+    "Write didn't use the forced line ending."
+  );
+  std::fs::remove_file(path).unwrap();
+  // Commands get the configured line endings, and their output is converted
+  let mut mock_ui = DummyUI{};
+  let mut mock_ui_lock = mock_ui.lock_ui();
+  io.command_line_ending = LineEnding::CRLF;
+  let output = io.run_transform_command(
+    &mut mock_ui_lock,
+    "tr '\\r' R".to_owned(),
+    &ShellContext::default(),
+    Box::new("a\nb\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert_eq!(&output, "aR\nbR\n", "Command input didn't use '\\r\\n'.");
+  let output = io.run_read_command(
+    &mut mock_ui_lock,
+    "printf 'a\\r\\nb\\r\\n'".to_owned(),
+    &ShellContext::default(),
+  ).unwrap();
+  assert_eq!(&output, "a\nb\n", "Command output wasn't converted to '\\n'.");
+}
+
+#[test]
+fn test_escaped_bytes_io() {
+  let mut io = LocalIO::new();
+  let path = "io_encoding_test_file";
+  // Latin-1 'ä', a private use char in the escape range and an incomplete char
+  let data: &[u8] = b"b\xe4r\n\xef\x9e\x80\nend\xc3";
+  std::fs::write(path, data).unwrap();
+  // By default invalid UTF-8 is an error
+  match io.read_file(path, true) {
+    Ok(_) => panic!("No error returned when reading invalid UTF-8."),
+    Err(e) => assert!(
+      matches!(e.downcast_ref::<LocalIOError>(), Some(LocalIOError::BadUtf8(_))),
+      "Wrong error when reading invalid UTF-8.",
+    ),
+  }
+  // When escaping the bytes survive a round trip
+  io.encoding = Encoding::EscapedBytes;
+  let read = io.read_file(path, true).unwrap();
+  assert_eq!(
+    read,
+    "b\u{f7e4}r\n\u{f7ef}\u{f79e}\u{f780}\nend\u{f7c3}",
+    "Invalid bytes weren't escaped as expected."
+  );
+  io.write_file(
+    path,
+    false, // don't append
+    LineEnding::LF,
+    Box::new(vec!["b\u{f7e4}r\n", "\u{f7ef}\u{f79e}\u{f780}\n", "end\u{f7c3}"]
+      .into_iter()
+    ).into(),
+  ).unwrap();
+  assert_eq!(
+    &std::fs::read(path).unwrap()[..],
+    data,
+    "Escaped bytes weren't written back as the original bytes."
+  );
+  std::fs::remove_file(path).unwrap();
+  // The same applies to commands
+  let mut mock_ui = DummyUI{};
+  let mut mock_ui_lock = mock_ui.lock_ui();
+  let output = io.run_transform_command(
+    &mut mock_ui_lock,
+    "cat".to_owned(),
+    &ShellContext::default(),
+    Box::new(vec!["b\u{f7e4}r\n"].into_iter()).into(),
+  ).unwrap();
+  assert_eq!(&output, "b\u{f7e4}r\n", "Command didn't round trip the bytes.");
+  io.encoding = Encoding::Utf8;
+  let res = io.run_read_command(
+    &mut mock_ui_lock,
+    "printf 'b\\344r'".to_owned(),
+    &ShellContext::default(),
+  );
+  assert!(res.is_err(), "Reading invalid UTF-8 from command should error.");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_atomic_save() {
+  use std::os::unix::fs::PermissionsExt;
+  let mut io = LocalIO::with_save_options(SaveOptions{
+    atomic: true,
+    backup: true,
+  });
+  let dir = "io_atomic_test_dir";
+  std::fs::create_dir_all(dir).unwrap();
+  let target = format!("{}/target", dir);
+  let link = format!("{}/link", dir);
+  std::fs::write(&target, "old\n").unwrap();
+  std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640))
+    .unwrap();
+  std::os::unix::fs::symlink("target", &link).unwrap();
+  // Writing via the symlink replaces the file it points to
+  io.write_file(
+    &link,
+    false, // don't append
+    LineEnding::LF,
+    Box::new("new\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert!(
+    std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink(),
+    "The symlink was replaced instead of followed."
+  );
+  assert_eq!(&std::fs::read_to_string(&target).unwrap(), "new\n");
+  assert_eq!(
+    std::fs::metadata(&target).unwrap().permissions().mode() & 0o777,
+    0o640,
+    "The file's permissions weren't kept."
+  );
+  assert_eq!(
+    &std::fs::read_to_string(format!("{}~", link)).unwrap(),
+    "old\n",
+    "No backup was made of the file before overwriting it."
+  );
+  // Appending is done in place, without backup
+  std::fs::remove_file(format!("{}~", link)).unwrap();
+  io.write_file(
+    &target,
+    true, // Append
+    LineEnding::LF,
+    Box::new("more\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert_eq!(&std::fs::read_to_string(&target).unwrap(), "new\nmore\n");
+  assert!(std::fs::metadata(format!("{}~", target)).is_err());
+  // Creating a new file works too, and no temporary files are left behind
+  io.write_file(
+    &format!("{}/created", dir),
+    false, // don't append
+    LineEnding::LF,
+    Box::new("data\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  // A dangling symlink is followed as well, creating the file it points to
+  let dangling = format!("{}/dangling", dir);
+  std::os::unix::fs::symlink("missing", &dangling).unwrap();
+  io.write_file(
+    &dangling,
+    false, // don't append
+    LineEnding::LF,
+    Box::new("data\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert!(
+    std::fs::symlink_metadata(&dangling).unwrap().file_type().is_symlink(),
+    "The dangling symlink was replaced instead of followed."
+  );
+  assert_eq!(
+    &std::fs::read_to_string(format!("{}/missing", dir)).unwrap(),
+    "data\n",
+  );
+  let mut files: Vec<String> = std::fs::read_dir(dir).unwrap()
+    .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+    .collect()
+  ;
+  files.sort();
+  assert_eq!(files, vec!["created", "dangling", "link", "missing", "target"]);
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_file_changed() {
+  let mut io = LocalIO::new();
+  let path = "io_changed_test_file";
+  std::fs::write(path, "1\n").unwrap();
+  assert!(!io.file_changed(path).unwrap(), "Unread file reported as changed.");
+  io.read_file(path, true).unwrap();
+  assert!(!io.file_changed(path).unwrap(), "Read file reported as changed.");
+  // Changed by something else
+  std::fs::write(path, "1\n2\n").unwrap();
+  assert!(io.file_changed(path).unwrap(), "Change wasn't detected.");
+  // Appending doesn't hide the change, but overwriting does
+  io.write_file(
+    path,
+    true, // Append
+    LineEnding::LF,
+    Box::new("3\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert!(io.file_changed(path).unwrap(), "Appending hid the change.");
+  io.write_file(
+    path,
+    false, // don't append
+    LineEnding::LF,
+    Box::new("1\n".split_inclusive('\n')).into(),
+  ).unwrap();
+  assert!(!io.file_changed(path).unwrap(), "Written file reported as changed.");
+  std::fs::remove_file(path).unwrap();
+  assert!(!io.file_changed(path).unwrap(), "Removed file reported as changed.");
+}
+
+#[test]
+fn test_shell_context() {
+  let mut io = LocalIO::new();
+  let mut mock_ui = DummyUI{};
+  let mut mock_ui_lock = mock_ui.lock_ui();
+  let context = ShellContext{
+    file: "some file".to_owned(),
+    selection: (2,3),
+    buffer_len: 5,
+    prev_shell_command: "echo hi".to_owned(),
+    scripted: true,
+  };
+  let output = io.run_read_command(
+    &mut mock_ui_lock,
+    concat!(
+      "printf '%s\\n' \"$ED_FILE\" \"$ED_SEL_START\" \"$ED_SEL_END\" ",
+      "\"$ED_BUFFER_LEN\" \"$ED_PREV_SHELL_COMMAND\" \"$ED_SCRIPTED\"",
+    ).to_owned(),
+    &context,
+  ).unwrap();
+  assert_eq!(
+    &output,
+    "some file\n2\n3\n5\necho hi\n1\n",
+    "The command didn't get the context as environment variables."
+  );
+}
+
+#[test]
+fn test_command_timeout() {
+  let mut io = LocalIO::new();
+  io.command_timeout = Some(std::time::Duration::from_millis(200));
+  // Commands finishing in time aren't affected
+  let mut mock_ui = DummyUI{};
+  let output = io.run_read_command(
+    &mut mock_ui.lock_ui(),
+    "echo hi".to_owned(),
+    &ShellContext::default(),
+  ).unwrap();
+  assert_eq!(&output, "hi\n");
+  // A transform that times out leaves the buffer unchanged
+  let macros = std::collections::HashMap::new();
+  let mut ui = crate::ui::ScriptedUI{
+    input: vec!["data\n".to_owned(), ".\n".to_owned()].into(),
+    print_ui: None,
+  };
+  let mut ed = crate::Ed::new(&mut io, &macros);
+  ed.run_command(&mut ui, "a").unwrap();
+  let start = std::time::Instant::now();
+  let res = ed.run_command(&mut ui, ",|sleep 10; echo replaced");
+  assert!(
+    start.elapsed() < std::time::Duration::from_secs(5),
+    "The command wasn't killed when timing out."
+  );
+  match res {
+    Ok(_) => panic!("No error returned when the command timed out."),
+    Err(crate::EdError::IO(e)) => assert_eq!(
+      e.downcast_ref::<LocalIOError>(),
+      Some(&LocalIOError::ChildTimedOut(std::time::Duration::from_millis(200))),
+      "Wrong error when the command timed out."
+    ),
+    Err(e) => panic!("Wrong error when the command timed out: {:?}", e),
+  }
+  assert_eq!(
+    ed.history.current().get_lines((1,1)).unwrap().collect::<Vec<_>>(),
+    vec!["data\n"],
+    "The buffer was changed by the timed out command."
+  );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_command_cancel() {
+  use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+  let mut io = LocalIO::new();
+  let marker = "io_cancel_test_marker";
+  let flag = Arc::new(AtomicBool::new(true));
+  let mut mock_ui = DummyUI{};
+  // The flag is cleared when given to the lock, so a stale cancel is ignored
+  let mut mock_ui_lock = mock_ui.lock_ui().with_cancel_flag(flag.clone());
+  assert!(!mock_ui_lock.is_cancelled());
+  let canceller = {
+    let flag = flag.clone();
+    std::thread::spawn(move || {
+      std::thread::sleep(std::time::Duration::from_millis(200));
+      flag.store(true, Ordering::SeqCst);
+    })
+  };
+  let start = std::time::Instant::now();
+  // The background job must be killed with the rest of the process group
+  let res = io.run_transform_command(
+    &mut mock_ui_lock,
+    format!("(sleep 1; touch {}) & sleep 10", marker),
+    &ShellContext::default(),
+    Box::new("data\n".split_inclusive('\n')).into(),
+  );
+  canceller.join().unwrap();
+  assert!(
+    start.elapsed() < std::time::Duration::from_secs(5),
+    "The command wasn't killed when cancelled."
+  );
+  match res {
+    Ok(_) => panic!("No error returned when the command was cancelled."),
+    Err(e) => assert_eq!(
+      e.downcast_ref::<LocalIOError>(),
+      Some(&LocalIOError::ChildCancelled),
+      "Wrong error when the command was cancelled."
+    ),
+  }
+  std::thread::sleep(std::time::Duration::from_millis(1500));
+  assert!(
+    std::fs::metadata(marker).is_err(),
+    "The command's background job survived the cancel."
+  );
+}
+
+// Like test_command_cancel, for the commands passing stdin through
+#[cfg(unix)]
+#[test]
+fn test_command_timeout_group() {
+  let mut io = LocalIO::new();
+  let timeout = std::time::Duration::from_millis(200);
+  io.command_timeout = Some(timeout);
+  let marker = "io_timeout_group_test_marker";
+  let command = format!("(sleep 1; touch {}); true", marker);
+  let mut mock_ui = DummyUI{};
+  let mut mock_ui_lock = mock_ui.lock_ui();
+  let results = [
+    io.run_command(
+      &mut mock_ui_lock,
+      command.clone(),
+      &ShellContext::default(),
+    ).map(|_| String::new()),
+    io.run_read_command(
+      &mut mock_ui_lock,
+      command,
+      &ShellContext::default(),
+    ),
+  ];
+  for res in results {
+    match res {
+      Ok(_) => panic!("No error returned when the command timed out."),
+      Err(e) => assert_eq!(
+        e.downcast_ref::<LocalIOError>(),
+        Some(&LocalIOError::ChildTimedOut(timeout)),
+        "Wrong error when the command timed out."
+      ),
+    }
+  }
+  std::thread::sleep(std::time::Duration::from_millis(1500));
+  assert!(
+    std::fs::metadata(marker).is_err(),
+    "The command's subshell survived the timeout."
+  );
+}
+
+// Cancelling while input is still being piped in must not panic the thread
+// writing it, which fails as the pipe closes
+#[cfg(unix)]
+#[test]
+fn test_command_cancel_piping() {
+  use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+  let mut io = LocalIO::new();
+  let flag = Arc::new(AtomicBool::new(false));
+  let mut mock_ui = DummyUI{};
+  let mut mock_ui_lock = mock_ui.lock_ui().with_cancel_flag(flag.clone());
+  let canceller = {
+    let flag = flag.clone();
+    std::thread::spawn(move || {
+      std::thread::sleep(std::time::Duration::from_millis(200));
+      flag.store(true, Ordering::SeqCst);
+    })
+  };
+  let res = io.run_write_command(
+    &mut mock_ui_lock,
+    "sleep 10".to_owned(),
+    &ShellContext::default(),
+    // Much more than fits in a pipe's buffer
+    Box::new(std::iter::repeat_n("data\n", 100_000)).into(),
+  );
+  canceller.join().unwrap();
+  match res {
+    Ok(_) => panic!("No error returned when the command was cancelled."),
+    Err(e) => assert_eq!(
+      e.downcast_ref::<LocalIOError>(),
+      Some(&LocalIOError::ChildCancelled),
+      "Wrong error when the command was cancelled."
+    ),
+  }
+}
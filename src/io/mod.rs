@@ -15,6 +15,73 @@ pub mod local_io;
 #[cfg(feature = "local_io")]
 pub use local_io::LocalIO;
 
+/// The line ending style of a file
+///
+/// Detected by the editor when reading a file and given back to
+/// [`IO::write_file`], so files with "\r\n" line endings keep them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+  /// `"\n"`, as used on Unix.
+  #[default]
+  LF,
+  /// `"\r\n"`, as used on Windows.
+  CRLF,
+}
+impl LineEnding {
+  /// Detect the line ending used in the given text, by its first line ending
+  ///
+  /// Returns `None` if the text has no line endings.
+  pub fn detect(text: &str) -> Option<Self> {
+    let i = text.find('\n')?;
+    if text[..i].ends_with('\r') { Some(Self::CRLF) } else { Some(Self::LF) }
+  }
+  /// Convert a '\n' terminated line to use this line ending
+  pub fn apply(self, line: &str) -> std::borrow::Cow<'_, str> {
+    match self {
+      Self::CRLF if line.ends_with('\n') && !line.ends_with("\r\n") => {
+        format!("{}\r\n", &line[..line.len() - 1]).into()
+      },
+      _ => line.into(),
+    }
+  }
+}
+
+/// The editor's state when running a shell command
+///
+/// Given to all the [`IO`] command methods, so commands can adapt to what is
+/// being edited. [`LocalIO`] sets it as environment variables, as listed by
+/// [`ShellContext::env_vars`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShellContext {
+  /// The default file path, empty if none is set.
+  pub file: String,
+  /// The lines given to the command, or the current selection if it isn't
+  /// given any. 1-indexed and inclusive.
+  pub selection: (usize, usize),
+  /// The number of lines in the buffer.
+  pub buffer_len: usize,
+  /// The shell command run before this one, empty if none.
+  pub prev_shell_command: String,
+  /// Set if the command is run from a macro or `g`/`v`/`G`/`V`.
+  pub scripted: bool,
+}
+impl ShellContext {
+  /// Get the context as environment variables
+  ///
+  /// `ED_FILE`, `ED_SEL_START`, `ED_SEL_END`, `ED_BUFFER_LEN`,
+  /// `ED_PREV_SHELL_COMMAND` and `ED_SCRIPTED` (`1` if set, otherwise `0`).
+  pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+    vec![
+      ("ED_FILE", self.file.clone()),
+      ("ED_SEL_START", self.selection.0.to_string()),
+      ("ED_SEL_END", self.selection.1.to_string()),
+      ("ED_BUFFER_LEN", self.buffer_len.to_string()),
+      ("ED_PREV_SHELL_COMMAND", self.prev_shell_command.clone()),
+      ("ED_SCRIPTED", (self.scripted as u8).to_string()),
+    ]
+  }
+}
+
 /// Trait that abstracts file interactions and running shell commands
 ///
 /// Intended to allow modifying how and where system interactions occur.
@@ -32,6 +99,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // The editor's state, to give to the command
+    context: &ShellContext,
   ) -> Result<()>;
 
   /// Run a read command, collecting stdout to add into buffer
@@ -46,6 +115,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // The editor's state, to give to the command
+    context: &ShellContext,
   ) -> Result<String>;
 
   /// Run a write command, receiving part of buffer via stdin
@@ -62,6 +133,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // The editor's state, to give to the command
+    context: &ShellContext,
     // Iterator over string slices to send over stdin
     input: LinesIter,
   ) -> Result<usize>;
@@ -83,6 +156,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // The editor's state, to give to the command
+    context: &ShellContext,
     // Iterator over string slices to send over stdin
     input: LinesIter,
   ) -> Result<String>;
@@ -91,14 +166,16 @@ pub trait IO {
   ///
   /// Returns number of bytes written
   ///
-  /// The LinesIter contains string slices over '\n' terminated lines. If you
-  /// with to write "\r\n" line endings into the file this should be handled in
-  /// the IO implementation.
+  /// The LinesIter contains string slices over '\n' terminated lines. They
+  /// should be written with the given line ending, which is the one detected
+  /// when the buffer was read from file ('\n' otherwise).
   fn write_file(&mut self,
     // Path to file as give by user. Not checked beyond shell escape parsing
     path: &str,
     // If appending
     append: bool,
+    // The line ending to write
+    line_ending: LineEnding,
     // Data to write to file
     data: LinesIter,
   ) -> Result<usize>;
@@ -106,11 +183,64 @@ pub trait IO {
   /// Normal file read
   ///
   /// The returned string will be split into lines and added into the buffer.
-  /// All line endings are converted into '\n' when adding into the buffer.
+  /// Line endings should be returned as they are in the file, so the editor can
+  /// detect which is used. All line endings are converted into '\n' when adding
+  /// into the buffer.
   fn read_file(&mut self,
     // Path to file as given by user. Not checked beyond shell escape parsing
     path: &str,
     // If true the method should error if no file is found at path
     must_exist: bool,
   ) -> Result<String>;
+
+  /// Check if the file at given path has changed since it was last read or
+  /// written via this IO
+  ///
+  /// Used by `w` to not overwrite changes made by other programs. Should return
+  /// false for files that haven't been read or written, or no longer exist. The
+  /// default implementation never detects any changes.
+  fn file_changed(&mut self,
+    // Path to file as given by user. Not checked beyond shell escape parsing
+    _path: &str,
+  ) -> Result<bool> {
+    Ok(false)
+  }
+
+  /// Persist undo history for the file at given path
+  ///
+  /// Used by [`crate::Ed::save_history`]. Where the data is stored is up to the
+  /// implementation, but [`history_path`] is recommended. The default
+  /// implementation discards the data.
+  fn write_history(&mut self,
+    // Path to the file the history is for
+    _path: &str,
+    // Serialized history to persist
+    _data: &str,
+  ) -> Result<()> {
+    Ok(())
+  }
+
+  /// Read persisted undo history for the file at given path
+  ///
+  /// Used by [`crate::Ed::load_history`]. Should return `None` if there is no
+  /// persisted history for the file. The default implementation never finds
+  /// any.
+  fn read_history(&mut self,
+    // Path to the file the history is for
+    _path: &str,
+  ) -> Result<Option<String>> {
+    Ok(None)
+  }
+}
+
+/// Get the path to persist undo history at for the file at given path
+///
+/// It is a hidden file in the same directory, `dir/file` giving
+/// `dir/.file.ed-history`.
+pub fn history_path(path: &str) -> String {
+  let path = std::path::Path::new(path);
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!(".{}.ed-history", name))
+    .to_string_lossy()
+    .into_owned()
 }
@@ -53,6 +53,7 @@ pub use error::{
 };
 
 mod cmd;
+pub use cmd::RegexFlags;
 
 pub mod ui;
 use ui::{UI, UILock, ScriptedUI};
@@ -60,9 +61,18 @@ pub mod io;
 use io::IO;
 
 mod history;
-pub use history::History;
+pub use history::{History, HistoryMode};
+use history::Snapshot;
 pub mod macros;
-use macros::{Macro, MacroGetter};
+use macros::{Macro, MacroGetter, MacroSnapshottingMode, MacroErrorHandling};
+pub mod plugins;
+use plugins::PluginGetter;
+pub mod extender;
+use extender::CommandExtender;
+pub mod buffers;
+use buffers::BufferManager;
+pub mod regex_engine;
+use regex_engine::RegexEngine;
 
 pub use buffer::iters::*;
 mod buffer;
@@ -78,10 +88,17 @@ pub use buffer::{
 pub struct Substitution {
   /// Regex pattern to match against
   pub pattern: String,
+  /// Flags configuring how the pattern matches
+  pub regex_flags: RegexFlags,
   /// Substitution template to replace it with
   pub substitute: String,
   /// Set true to apply to all occurences (instead of only the first)
   pub global: bool,
+  /// Which occurence to replace, starting from 1 (if `global` this and all
+  /// following occurences are replaced)
+  pub occurrence: usize,
+  /// Set true to ask the UI before each replacement
+  pub confirm: bool,
   /// Flag to print after execution
   pub p: bool,
   /// Flag to print with line numbers after execution
@@ -90,6 +107,17 @@ pub struct Substitution {
   pub l: bool,
 }
 
+/// A regex match within a line, as returned by [`Ed::find_matches`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineMatch {
+  /// The line the match is on (1-indexed)
+  pub line: usize,
+  /// Byte index in the line's text where the match starts
+  pub start: usize,
+  /// Byte index in the line's text where the match ends (exclusive)
+  pub end: usize,
+}
+
 /// The state variable used to track the editor's internal state.
 ///
 /// It is designed to support mutation and analysis by library users, but be
@@ -104,6 +132,20 @@ pub struct Ed <'a> {
   /// Uses a special [`Buffer`] analogue over [`PubLine`], since some of the
   /// internal data in Line could cause unexpected behavior if pasted as is.
   pub clipboard: Clipboard,
+  /// The named registers, an alternative to the clipboard
+  ///
+  /// Commands that write to or read from the clipboard can be given a register
+  /// name instead (as `"<name>` right after the command), in which case this is
+  /// used instead of `clipboard`.
+  pub registers: std::collections::HashMap<char, Clipboard>,
+  /// Previous clipboard contents, most recent first
+  ///
+  /// When the clipboard is replaced its old contents are pushed here, so they
+  /// can still be pasted by giving `x`/`X` an index. Replace the clipboard via
+  /// [`Ed::set_clipboard`] to do the same from outside.
+  pub clipboard_history: std::collections::VecDeque<Clipboard>,
+  /// How many previous clipboards to keep in `clipboard_history`.
+  pub clipboard_history_limit: usize,
   /// Tracks the currently selected lines in the buffer.
   ///
   /// Inclusive 1-indexed start and end bounds over selected lines. Selected
@@ -119,6 +161,22 @@ pub struct Ed <'a> {
   pub io: &'a mut dyn IO,
   /// The path to the currently selected file.
   pub file: String,
+  /// Set if the file read into the buffer lacked a newline after its last
+  /// line.
+  ///
+  /// If set, `w` writes the whole buffer without a newline after the last line,
+  /// so the file is saved as it was read.
+  pub missing_newline: bool,
+  /// The line ending used in the file read into the buffer.
+  ///
+  /// Given to the [`IO`] when writing, so `w` saves files (also under another
+  /// name) with the line ending they were read with.
+  pub line_ending: io::LineEnding,
+  /// The buffers open besides the active one
+  ///
+  /// The active buffer is the one in `history`, `file` and `selection`. See
+  /// [`BufferManager`] for how to manage the others.
+  pub buffers: BufferManager,
 
   /// Shell command last given by the user
   ///
@@ -126,14 +184,45 @@ pub struct Ed <'a> {
   /// similar should already have occured before saving here.
   /// (Currently saved before successful run, so may be invalid).
   pub prev_shell_command: String,
+  /// Set to the path `w` refused to overwrite since the file was changed by
+  /// something else, so repeating the command directly overwrites it anyway.
+  ///
+  /// Cleared by any other command.
+  pub changed_file_warning: Option<String>,
   /// The previous `s` commands arguments, to support repeating last `s` command
   /// when no arguments are given to `s`.
   pub prev_s: Option<Substitution>,
+  /// The last regex given to a pattern index, `s` or `g`/`v`/`G`/`V`, with
+  /// its flags, to support reusing it when an empty regex is given.
+  pub prev_pattern: Option<(String, RegexFlags)>,
+  // Set while running the commands of a macro or 'g'/'v'/'G'/'V', to tell
+  // shell commands they are scripted. (Not the recursion depth, as macros run
+  // via Ed::run_macro start at depth 0.)
+  scripted: bool,
 
   /// Configuration of prefix before command input.
   ///
   /// Traditionally ':' so set to that by default.
   pub cmd_prefix: Option<char>,
+  /// Set to make `s` behave like in GNU Ed.
+  ///
+  /// If set `s` accepts a number among its flags for which match to replace,
+  /// and uses GNU Ed's replacement syntax: `&` for the whole match, `\1` to
+  /// `\9` for capture groups and a lone `%` to repeat the previous replacement.
+  /// If not set the replacement uses the regex crate's syntax (`$1`).
+  pub gnu_substitute: bool,
+  /// Set to let pattern indices wrap around the buffer, like in GNU Ed.
+  ///
+  /// If set `/re/` continues from the first line after passing the last line
+  /// (and `?re?` vice versa), ending with the line it started from.
+  pub wrap_search: bool,
+  /// Set when a pattern index wrapped around the buffer during the latest
+  /// command.
+  ///
+  /// Reset at the start of every command run via [`Ed::run_command`] or
+  /// [`Ed::get_and_run_command`], so a UI can check it afterwards to notify
+  /// the user. A `Cell`, since indices are interpreted without mutable access.
+  pub search_wrapped: std::cell::Cell<bool>,
   /// Set default to print numbered lines.
   ///
   /// If set `n` printing flag behaviour inverts and disables line numbers.
@@ -156,12 +245,26 @@ pub struct Ed <'a> {
   pub error: Option<EdError>,
   /// EXPERIMENTAL: Reference to accessor for macros.
   pub macro_getter: &'a dyn MacroGetter,
+  /// Reference to accessor for plugins, run by the `@` command.
+  pub plugin_getter: &'a dyn PluginGetter,
+  /// Handler for commands not defined in add-ed.
+  pub command_extender: &'a dyn CommandExtender,
+  /// The regex engine used to compile all regexes matched against the buffer.
+  pub regex_engine: &'a dyn RegexEngine,
   /// Set how many recursions should be allowed.
   ///
   /// One recursion is counted as one macro or 'g'/'v'/'G'/'V' invocation. Under
   /// 2 is likely to interfere with basic use, 4 will require that macros don't
   /// call into eachother, 16 is unlikely to abort needlessly.
   pub recursion_limit: usize,
+  /// Set to persist undo history beside the edited file.
+  ///
+  /// If set, `w` saves the history (via [`Ed::save_history`]) after writing the
+  /// whole buffer to a file and `e` tries to load it (via
+  /// [`Ed::load_history`]) after reading in a file. Requires an [`IO`] that
+  /// implements the history methods.
+  #[cfg(feature = "serde")]
+  pub persist_history: bool,
 }
 
 impl <'a, > Ed <'a> {
@@ -169,13 +272,26 @@ impl <'a, > Ed <'a> {
   ///
   /// Defaults are as follow:
   /// - `file`: empty string
+  /// - `missing_newline`: `false`
+  /// - `line_ending`: `LineEnding::LF`
+  /// - `buffers`: no buffers besides the active one
   /// - `clipboard`: empty clipboard
+  /// - `registers`: no registers
+  /// - `clipboard_history`: empty
+  /// - `clipboard_history_limit`: `16`
   /// - `error`: `None`
   /// - `print_errors`: `true`
   /// - `n`: `false`,
   /// - `l`: `false`,
   /// - `cmd_prefix`: `Some(':')`
+  /// - `gnu_substitute`: `false`
+  /// - `wrap_search`: `true`
+  /// - `search_wrapped`: `false`
   /// - `recursion_limit`: `16`
+  /// - `plugin_getter`: `&()`, which holds no plugins
+  /// - `command_extender`: `&()`, which defines no commands
+  /// - `regex_engine`: `&RustRegex`, using the `regex` crate
+  /// - `persist_history`: `false`
   pub fn new(
     io: &'a mut dyn IO,
     macro_getter: &'a dyn MacroGetter,
@@ -186,22 +302,111 @@ impl <'a, > Ed <'a> {
       selection,
       history: History::new(),
       prev_s: None,
+      prev_pattern: None,
+      changed_file_warning: None,
       prev_shell_command: String::new(),
+      scripted: false,
       // Sane defaults for externally visible variables
       file: String::new(),
+      missing_newline: false,
+      line_ending: io::LineEnding::LF,
+      buffers: BufferManager::new(),
       clipboard: Clipboard::new(),
+      registers: std::collections::HashMap::new(),
+      clipboard_history: std::collections::VecDeque::new(),
+      clipboard_history_limit: 16,
       error: None,
       print_errors: true,
       n: false,
       l: false,
       cmd_prefix: Some(':'),
+      gnu_substitute: false,
+      wrap_search: true,
+      search_wrapped: std::cell::Cell::new(false),
       recursion_limit: 16,
+      plugin_getter: &(),
+      command_extender: &(),
+      regex_engine: &regex_engine::RustRegex,
+      #[cfg(feature = "serde")]
+      persist_history: false,
       // And the given values
       io,
       macro_getter,
     }
   }
 
+  /// Configure which [`HistoryMode`] to use
+  ///
+  /// Replaces the history with a new one in the given mode, so this should be
+  /// done directly upon construction.
+  pub fn history_mode(mut self, mode: HistoryMode) -> Self {
+    self.history = History::with_mode(mode);
+    self
+  }
+
+  /// Replace the clipboard, pushing its old contents into `clipboard_history`
+  ///
+  /// Empty clipboards aren't saved, and the oldest entries are dropped to stay
+  /// within `clipboard_history_limit`.
+  pub fn set_clipboard(&mut self, data: Clipboard) {
+    let old = std::mem::replace(&mut self.clipboard, data);
+    if !old.is_empty() {
+      self.clipboard_history.push_front(old);
+    }
+    self.clipboard_history.truncate(self.clipboard_history_limit);
+  }
+
+  /// Get an entry in the clipboard ring
+  ///
+  /// Index 0 is the current clipboard, 1 the clipboard before it, and so on.
+  /// Returns None if there is no entry at that index.
+  pub fn clipboard_entry(&self, index: usize) -> Option<&Clipboard> {
+    match index {
+      0 => Some(&self.clipboard),
+      i => self.clipboard_history.get(i - 1),
+    }
+  }
+
+  /// Check if the current file was changed by something else since it was read
+  /// or written
+  ///
+  /// Lets UIs warn about it before `w` refuses to overwrite the file. Always
+  /// false if no file is set, or if the [`IO`] doesn't detect changes.
+  pub fn file_changed(&mut self) -> Result<bool> {
+    if self.file.is_empty() { return Ok(false); }
+    Ok(self.io.file_changed(&self.file)?)
+  }
+
+  /// Find all matches for a regex within the given selection
+  ///
+  /// Intended for UIs to highlight matches with. The matches are found as `s`
+  /// finds them, so a match may span several lines (it is then returned as one
+  /// [`LineMatch`] per line it covers). An empty pattern reuses the previous
+  /// regex, as with commands.
+  pub fn find_matches(
+    &self,
+    pattern: &str,
+    flags: RegexFlags,
+    selection: (usize, usize),
+  ) -> Result<Vec<LineMatch>> {
+    cmd::find_matches(self, pattern, flags, selection)
+  }
+
+  /// Get the lines the selection would be replaced by if `s` was run with the
+  /// given [`Substitution`]
+  ///
+  /// Intended for UIs to preview substitutions with. Doesn't modify the buffer
+  /// or history, and replaces without asking even if `confirm` is set. An empty
+  /// pattern and (in GNU mode) a `%` replacement are resolved like `s` does.
+  /// Errors like `s` if the regex doesn't match within the selection.
+  pub fn preview_substitution(
+    &self,
+    substitution: &Substitution,
+    selection: (usize, usize),
+  ) -> Result<Vec<String>> {
+    cmd::preview_substitution(self, substitution, selection)
+  }
+
   /// Run the given command
   ///
   /// Returns true if the command was to quit
@@ -266,10 +471,27 @@ impl <'a, > Ed <'a> {
     }
   }
 
+  // Same as private_get_and_run_command, but marks the command as scripted.
+  // For running the commands of macros and 'g'/'v'/'G'/'V'.
+  fn private_get_and_run_scripted_command(
+    &mut self,
+    ui: &mut dyn UI,
+    recursion_depth: usize,
+  ) -> Result<bool> {
+    let orig_scripted = std::mem::replace(&mut self.scripted, true);
+    let res = self.private_get_and_run_command(ui, recursion_depth);
+    self.scripted = orig_scripted;
+    res
+  }
+
   /// Run given macro until Ed receives a command to quit or errors
   ///
   /// Will immediately return error if the macro was given wrong nr of arguments
   ///
+  /// Undo snapshots are created according to the macro's
+  /// [`MacroSnapshottingMode`], where a snapshot for the whole macro is
+  /// labelled "Macro execution".
+  ///
   /// Returns Ok(()) when quit by command (or end of macro input)
   pub fn run_macro<
     S: std::ops::Deref<Target = str>,
@@ -279,7 +501,7 @@ impl <'a, > Ed <'a> {
     mac: &Macro,
     arguments: &[S],
   ) -> Result<()> {
-    self.private_run_macro(ui, mac, arguments, 0)
+    self.private_run_macro(ui, mac, arguments, "Macro execution", 0)
   }
   // Exists to handle nesting depth, for nested ':' invocations, without
   // exposing that argument to the public interface (since it will always be 0
@@ -291,24 +513,139 @@ impl <'a, > Ed <'a> {
     ui: &mut dyn UI,
     mac: &Macro,
     arguments: &[S],
+    label: &str,
     recursion_depth: usize,
   ) -> Result<()> {
     // Apply the arguments via templating
     let to_run = macros::apply_arguments(mac, arguments)?;
+    if mac.snapshotting_mode == MacroSnapshottingMode::ExposeModifications {
+      return self.private_run_script(ui, &to_run, mac.error_handling, recursion_depth);
+    }
+    // Before disabling snapshotting, create one for this invocation
+    self.history.snapshot(label.into());
+    // Disable undo snapshotting during macro execution
+    let orig_dont_snapshot = self.history.dont_snapshot;
+    self.history.dont_snapshot = true;
+    // Keep the state to revert to if requested (cheap, as lines are shared)
+    let before = self.history.current().create_snapshot();
+    let selection_before = self.selection;
+    let revert = mac.snapshotting_mode == MacroSnapshottingMode::RevertMutation;
+    let clipboards_before = revert.then(|| (
+      self.clipboard.clone(),
+      self.clipboard_history.clone(),
+      self.registers.clone(),
+    ));
+    let res = self.private_run_script(ui, &to_run, mac.error_handling, recursion_depth);
+    if revert {
+      if self.history.current() != &before {
+        // Snapshotting is still disabled, so this modifies in place
+        *self.history.current_mut(label.into()) = before;
+      }
+      // The selection may not be valid for the reverted buffer
+      self.selection = selection_before;
+    }
+    // Nor should cutting or copying in the macro leave a trace
+    if let Some((clipboard, history, registers)) = clipboards_before {
+      self.clipboard = clipboard;
+      self.clipboard_history = history;
+      self.registers = registers;
+    }
+    // Re-enable snapshotting after
+    self.history.dont_snapshot = orig_dont_snapshot;
+    // If snapshotting was originally enabled we should handle if no
+    // mutation of the buffer occured during the dont_snapshot.
+    if !orig_dont_snapshot { self.history.dedup_present(); }
+    res
+  }
+  // Run the given script (newline separated commands) without any special
+  // snapshot handling.
+  fn private_run_script(
+    &mut self,
+    ui: &mut dyn UI,
+    to_run: &str,
+    error_handling: MacroErrorHandling,
+    recursion_depth: usize,
+  ) -> Result<()> {
     // Construct a dummy UI with the given input
     let mut script_ui = ScriptedUI{
       input: to_run.lines().map(|x| format!("{}\n",x)).collect(),
       print_ui: Some(ui),
     };
     // Loop over it, handling errors, until quit received
-    loop {
-      if self.private_get_and_run_command(&mut script_ui, recursion_depth)? {
-        break;
+    let mut ignored = Vec::new();
+    let res = loop {
+      match self.private_get_and_run_scripted_command(
+        &mut script_ui,
+        recursion_depth,
+      ) {
+        Ok(true) => break Ok(()),
+        Ok(false) => {},
+        Err(e) if !error_handling.aborts_on(&e) => ignored.push(e),
+        Err(e) => break Err(e),
       }
+    };
+    // Report the ignored errors, even if aborted by a later error
+    if !ignored.is_empty() {
+      let mut message = format!(
+        "Ignored {} error(s) during macro execution:",
+        ignored.len(),
+      );
+      for e in ignored {
+        message.push('\n');
+        message.push_str(&e.to_string());
+      }
+      script_ui.print_message(&message)?;
     }
+    res
+  }
+
+  /// Persist the undo history for the current file
+  ///
+  /// Serializes the history and hands it to the [`IO`] to store beside
+  /// `self.file`. Errors if no file is set.
+  #[cfg(feature = "serde")]
+  pub fn save_history(&mut self) -> Result<()> {
+    if self.file.is_empty() { return Err(EdError::DefaultFileUnset); }
+    let data = serde_json::to_string(&self.history)
+      .map_err(|e| EdError::HistoryInvalid(e.to_string()))
+    ?;
+    self.io.write_history(&self.file, &data)?;
     Ok(())
   }
 
+  /// Load persisted undo history for the current file
+  ///
+  /// The persisted history is only used if the snapshot it considers saved
+  /// matches the currently viewed buffer (meaning the file hasn't been changed
+  /// since the history was persisted). If so it replaces the current history,
+  /// viewing the saved snapshot, and true is returned.
+  ///
+  /// Errors if no file is set or if the persisted history is invalid.
+  #[cfg(feature = "serde")]
+  pub fn load_history(&mut self) -> Result<bool> {
+    if self.file.is_empty() { return Err(EdError::DefaultFileUnset); }
+    let data = match self.io.read_history(&self.file)? {
+      Some(data) => data,
+      None => return Ok(false),
+    };
+    let mut loaded: History<Buffer> = serde_json::from_str(&data)
+      .map_err(|e| EdError::HistoryInvalid(e.to_string()))
+    ?;
+    let saved_i = match loaded.saved_i() {
+      Some(i) => i,
+      None => return Ok(false),
+    };
+    // Compare only the text, since tags aren't saved to the file
+    let matches = loaded.snapshots()[saved_i].1.iter().map(|l| &l.text[..])
+      .eq(self.history.current().iter().map(|l| &l.text[..]))
+    ;
+    if !matches { return Ok(false); }
+    loaded.set_viewed_i(saved_i)?;
+    loaded.dont_snapshot = self.history.dont_snapshot;
+    self.history = loaded;
+    Ok(true)
+  }
+
   /// Run until quit by command
   ///
   /// Prints ? or error message as errors occur (depending on `print_errors`).
@@ -2,22 +2,78 @@ use std::borrow::Cow;
 
 use crate::{Result, EdError};
 
-// TODO, enable this later
-///// How to handle undo/redo snapshotting during macro execution
-//pub enum MacroSnapshottingMode {
-//  /// The default mode, same behaviour as the 'g' command
-//  ///
-//  /// Will squash modifications into the invocation itself *AND* remove that
-//  /// snapshot if it isn't changed from the previous.
-//  Default,
-//  /// Any modifications to the buffer are rollbacked after execution
-//  RevertMutation,
-//  /// Any modifications are shown as caused by the macro invocation
-//  SquashModifications,
-//  /// Any modifications are shown as caused by the modifying command in the
-//  /// macro
-//  ExposeModifications,
-//}
+/// How to handle undo/redo snapshotting during macro execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature="serde", serde(rename_all="lowercase"))]
+pub enum MacroSnapshottingMode {
+  /// Any modifications to the buffer are rollbacked after execution
+  ///
+  /// For macros that only report on the buffer, such as printing statistics.
+  /// (The rollback is done without creating a snapshot, so no trace of the
+  /// modifications is left in the undo history. The clipboard, clipboard
+  /// history and registers are restored as well.)
+  RevertMutation,
+  /// The default mode, same behaviour as the 'g' command
+  ///
+  /// Any modifications are shown as caused by the macro invocation, squashed
+  /// into one snapshot *AND* that snapshot is removed if it isn't changed from
+  /// the previous.
+  #[default]
+  SquashModifications,
+  /// Any modifications are shown as caused by the modifying command in the
+  /// macro
+  ///
+  /// Each modifying command can thus be undone on its own. (If the macro is
+  /// run by a macro or `g` that squashes its modifications they are still
+  /// squashed into that invocation.)
+  ExposeModifications,
+}
+impl MacroSnapshottingMode {
+  #[cfg(feature="serde")]
+  fn is_default(&self) -> bool {
+    self == &Self::default()
+  }
+}
+
+/// How to handle errors during macro execution
+///
+/// Errors from the UI are always returned, as the macro can't continue without
+/// it. Ignored errors are collected and printed when the macro finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature="serde", serde(rename_all="lowercase"))]
+pub enum MacroErrorHandling {
+  /// The default mode, any error aborts the macro and is returned
+  #[default]
+  Abort,
+  /// Abort on any error except those caused by not finding anything to act on
+  ///
+  /// (That is [`EdError::RegexNoMatch`], [`EdError::TagNoMatch`] and
+  /// [`EdError::NoOp`], which are ignored.)
+  AbortExceptNoMatch,
+  /// Ignore all errors, running the macro to its end
+  Ignore,
+}
+impl MacroErrorHandling {
+  #[cfg(feature="serde")]
+  fn is_default(&self) -> bool {
+    self == &Self::default()
+  }
+  /// Check if the given error should abort macro execution
+  pub fn aborts_on(&self, error: &EdError) -> bool {
+    match (self, error) {
+      (_, EdError::UI(_)) => true,
+      (Self::Abort, _) => true,
+      (
+        Self::AbortExceptNoMatch,
+        EdError::RegexNoMatch(_) | EdError::TagNoMatch(_) | EdError::NoOp,
+      ) => false,
+      (Self::AbortExceptNoMatch, _) => true,
+      (Self::Ignore, _) => false,
+    }
+  }
+}
 
 /// Small enum describing argument nr constraints
 ///
@@ -60,9 +116,23 @@ pub struct Macro {
   /// nr of arguments given, and if `None` is set no argument substitution is 
   /// run on the macro (which means '$'s don't need to be doubled in the macro).
   pub nr_arguments: NrArguments,
-  // TODO, enable this later
-  // /// How the macro execution interacts with undo/redo snapshotting
-  // snapshotting_mode: MacroSnapshottingMode,
+  /// How the macro execution interacts with undo/redo snapshotting
+  ///
+  /// See [`MacroSnapshottingMode`], defaults to squashing all modifications
+  /// into the macro invocation.
+  #[cfg_attr(feature="serde", serde(
+    default,
+    skip_serializing_if="MacroSnapshottingMode::is_default",
+  ))]
+  pub snapshotting_mode: MacroSnapshottingMode,
+  /// How errors during macro execution are handled
+  ///
+  /// See [`MacroErrorHandling`], defaults to aborting on any error.
+  #[cfg_attr(feature="serde", serde(
+    default,
+    skip_serializing_if="MacroErrorHandling::is_default",
+  ))]
+  pub error_handling: MacroErrorHandling,
 }
 impl Macro {
   /// Construct a macro
@@ -76,6 +146,8 @@ impl Macro {
     Self{
       input: input.into(),
       nr_arguments: NrArguments::Any,
+      snapshotting_mode: MacroSnapshottingMode::default(),
+      error_handling: MacroErrorHandling::default(),
     }
   }
   /// Configure required nr of arguments for the macro
@@ -83,6 +155,16 @@ impl Macro {
     self.nr_arguments = nr;
     self
   }
+  /// Configure how the macro execution interacts with undo/redo snapshotting
+  pub fn snapshotting_mode(mut self, mode: MacroSnapshottingMode) -> Self {
+    self.snapshotting_mode = mode;
+    self
+  }
+  /// Configure how errors during macro execution are handled
+  pub fn error_handling(mut self, handling: MacroErrorHandling) -> Self {
+    self.error_handling = handling;
+    self
+  }
 }
 
 /// Trait over different ways to get macros by name
@@ -0,0 +1,56 @@
+//! Plugin support for the `@` command
+
+use crate::{Ed, Result};
+use crate::ui::UI;
+
+/// A compiled in command, run by the `@` command
+///
+/// Unlike macros and shell commands a plugin works on the editor state
+/// directly, so it can modify the buffer without breaking the sharing of line
+/// data between snapshots or losing line tags.
+///
+/// The `@` command handles undo snapshotting the same way as `:`, squashing any
+/// modifications made by the plugin into one snapshot labelled with the
+/// invocation (and removing it if nothing was changed).
+///
+/// Since the plugin receives mutable access to the editor it is run through a
+/// shared reference. Use interior mutability if the plugin needs state.
+pub trait Plugin {
+  /// Run the plugin
+  ///
+  /// - `selection` is the interpreted and verified selection given to `@`, if
+  ///   any was given. (Use `ed.selection` to default to the current selection.)
+  /// - `arguments` are the whitespace separated arguments after the plugin
+  ///   name.
+  ///
+  /// The plugin is expected to set `ed.selection` to whatever it acted upon, if
+  /// it modifies the buffer.
+  fn run(
+    &self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    selection: Option<(usize, usize)>,
+    arguments: &[&str],
+  ) -> Result<()>;
+}
+
+/// Trait over different ways to get plugins by name
+///
+/// Same as [`MacroGetter`](crate::macros::MacroGetter), but for plugins. A
+/// ready implementation exists for HashMap and for `()`, which holds no
+/// plugins.
+pub trait PluginGetter {
+  fn get_plugin(&self, name: &str) -> Result<Option<&dyn Plugin>>;
+}
+
+impl PluginGetter for std::collections::HashMap<&str, Box<dyn Plugin>> {
+  fn get_plugin(&self, name: &str) -> Result<Option<&dyn Plugin>> {
+    Ok(self.get(name).map(|p| &**p))
+  }
+}
+
+impl PluginGetter for () {
+  fn get_plugin(&self, _name: &str) -> Result<Option<&dyn Plugin>> {
+    Ok(None)
+  }
+}
@@ -0,0 +1,163 @@
+//! Abstraction over the regex engine used for matching
+//!
+//! Pattern indices, `s` and `g`/`v`/`G`/`V` all compile their regexes through
+//! [`Ed.regex_engine`](crate::Ed::regex_engine). By default that is
+//! [`RustRegex`], using the `regex` crate. With the `fancy_regex` feature
+//! [`FancyRegex`] is also available, which supports lookaround and
+//! backreferences at the cost of worst case performance.
+
+use crate::{EdError, Result, RegexFlags};
+
+/// A match found by a [`CompiledRegex`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegexMatch {
+  /// Byte index of the start of the match
+  pub start: usize,
+  /// Byte index of the end of the match (exclusive)
+  pub end: usize,
+  /// The replacement template expanded for this match, if one was given
+  pub replacement: Option<String>,
+}
+
+/// Iterator over the matches found by [`CompiledRegex::find_iter`]
+pub type Matches<'a> = Box<dyn Iterator<Item = Result<RegexMatch>> + 'a>;
+
+/// A regex compiled by a [`RegexEngine`]
+pub trait CompiledRegex {
+  /// Check if the regex matches anywhere in the given text
+  fn is_match(&self, text: &str) -> Result<bool>;
+  /// Iterate over all non-overlapping matches in the given text, in order
+  ///
+  /// If a replacement template is given it is expanded for each match, using
+  /// the `regex` crate's syntax (`$1`, `${name}`, `$$` for a literal `$`).
+  /// Matches are found (and expanded) as the iterator is advanced, so callers
+  /// only interested in the first few don't pay for the rest.
+  fn find_iter<'a>(&'a self,
+    text: &'a str,
+    replacement: Option<&'a str>,
+  ) -> Matches<'a>;
+}
+
+/// Trait for compiling regexes
+///
+/// The compiled regex must apply the given [`RegexFlags`] and treat `^` and `$`
+/// as matching at the start and end of every line.
+pub trait RegexEngine {
+  /// Compile the given pattern with the given flags
+  fn compile(&self,
+    pattern: &str,
+    flags: RegexFlags,
+  ) -> Result<Box<dyn CompiledRegex>>;
+}
+
+/// The default [`RegexEngine`], using the `regex` crate
+///
+/// Guarantees linear time matching, but doesn't support lookaround or
+/// backreferences.
+pub struct RustRegex;
+impl RegexEngine for RustRegex {
+  fn compile(&self,
+    pattern: &str,
+    flags: RegexFlags,
+  ) -> Result<Box<dyn CompiledRegex>> {
+    let regex = regex::RegexBuilder::new(pattern)
+      .multi_line(true)
+      .case_insensitive(flags.case_insensitive)
+      .ignore_whitespace(flags.verbose)
+      .dot_matches_new_line(flags.dot_matches_newline)
+      .build()
+      .map_err(|e| EdError::regex_error(e, pattern))
+    ?;
+    Ok(Box::new(regex))
+  }
+}
+impl CompiledRegex for regex::Regex {
+  fn is_match(&self, text: &str) -> Result<bool> {
+    Ok(regex::Regex::is_match(self, text))
+  }
+  fn find_iter<'a>(&'a self,
+    text: &'a str,
+    replacement: Option<&'a str>,
+  ) -> Matches<'a> {
+    Box::new(self.captures_iter(text).map(move |captures| {
+      // Capture group 0 is always the whole match, so safe to unwrap
+      let whole = captures.get(0).unwrap();
+      Ok(RegexMatch{
+        start: whole.start(),
+        end: whole.end(),
+        replacement: replacement.map(|template| {
+          let mut expanded = String::new();
+          captures.expand(template, &mut expanded);
+          expanded
+        }),
+      })
+    }))
+  }
+}
+
+/// A backtracking [`RegexEngine`], using the `fancy-regex` crate
+///
+/// Supports lookaround and backreferences, but matching may be slow (and
+/// errors if it backtracks too much) for some patterns.
+#[cfg(feature = "fancy_regex")]
+pub struct FancyRegex;
+#[cfg(feature = "fancy_regex")]
+impl RegexEngine for FancyRegex {
+  fn compile(&self,
+    pattern: &str,
+    flags: RegexFlags,
+  ) -> Result<Box<dyn CompiledRegex>> {
+    // Apply the flags inline, as fancy-regex's builder doesn't support them all
+    let mut inline = String::from("(?m");
+    if flags.case_insensitive { inline.push('i'); }
+    if flags.verbose { inline.push('x'); }
+    if flags.dot_matches_newline { inline.push('s'); }
+    inline.push(')');
+    let regex = fancy_regex::Regex::new(&format!("{}{}", inline, pattern))
+      .map_err(|e| EdError::RegexEngineError{
+        regex: pattern.to_owned(),
+        error: e.to_string(),
+      })
+    ?;
+    Ok(Box::new(FancyCompiled{ regex, pattern: pattern.to_owned() }))
+  }
+}
+// Holds the pattern as given, for error messages
+#[cfg(feature = "fancy_regex")]
+struct FancyCompiled {
+  regex: fancy_regex::Regex,
+  pattern: String,
+}
+#[cfg(feature = "fancy_regex")]
+impl FancyCompiled {
+  fn error(&self, e: fancy_regex::Error) -> EdError {
+    EdError::RegexEngineError{
+      regex: self.pattern.clone(),
+      error: e.to_string(),
+    }
+  }
+}
+#[cfg(feature = "fancy_regex")]
+impl CompiledRegex for FancyCompiled {
+  fn is_match(&self, text: &str) -> Result<bool> {
+    self.regex.is_match(text).map_err(|e| self.error(e))
+  }
+  fn find_iter<'a>(&'a self,
+    text: &'a str,
+    replacement: Option<&'a str>,
+  ) -> Matches<'a> {
+    let expander = fancy_regex::Expander::default();
+    Box::new(self.regex.captures_iter(text).map(move |captures| {
+      let captures = captures.map_err(|e| self.error(e))?;
+      // Capture group 0 is always the whole match, so safe to unwrap
+      let whole = captures.get(0).unwrap();
+      Ok(RegexMatch{
+        start: whole.start(),
+        end: whole.end(),
+        replacement: replacement
+          .map(|template| expander.expansion(template, &captures))
+        ,
+      })
+    }))
+  }
+}
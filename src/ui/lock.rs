@@ -1,12 +1,21 @@
 use super::UI;
+use std::sync::{
+  Arc,
+  atomic::{AtomicBool, Ordering},
+};
 
 /// A simple object that locks UI and calls unlock_ui on it when being dropped
 /// (Since it holds a mutable ref to UI its existence locks UI interaction)
 ///
 /// Exists to provide a mutex mechanism for UIs which cannot do anything else
 /// while an [`crate::IO`] is using the process' tty.
+///
+/// A UI can also let the user cancel the shell command being run, by giving
+/// the lock a flag with [`UILock::with_cancel_flag`] and setting it (for
+/// example from a Ctrl-C handler).
 pub struct UILock<'a> {
   inner: &'a mut dyn UI,
+  cancel_flag: Option<Arc<AtomicBool>>,
 }
 impl <'a> UILock<'a> {
   /// Construct a lock containing the given mutable reference
@@ -14,7 +23,23 @@ impl <'a> UILock<'a> {
   /// Locks the given [`UI`] (due to the borrow checker) until the created lock
   /// is dropped, upon which it will call [`UI::unlock_ui`] before disappearing.
   pub fn new(ui: &'a mut dyn UI) -> Self {
-    Self{inner: ui}
+    Self{inner: ui, cancel_flag: None}
+  }
+  /// Add a flag which cancels the shell command being run when set
+  ///
+  /// The flag is cleared when given, so only setting it while locked cancels.
+  pub fn with_cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+    flag.store(false, Ordering::SeqCst);
+    self.cancel_flag = Some(flag);
+    self
+  }
+  /// If the UI can cancel the shell command being run
+  pub fn is_cancellable(&self) -> bool {
+    self.cancel_flag.is_some()
+  }
+  /// If the UI has asked to cancel the shell command being run
+  pub fn is_cancelled(&self) -> bool {
+    self.cancel_flag.as_ref().is_some_and(|f| f.load(Ordering::SeqCst))
   }
 }
 impl Drop for UILock<'_> {
@@ -2,6 +2,7 @@ use crate::{
   Ed,
   ui::UI,
   ui::UILock,
+  ui::Confirmation,
 };
 use super::Result;
 
@@ -17,6 +18,9 @@ pub struct Print {
 }
 
 /// A mock UI that logs all prints and panics when asked for input.
+///
+/// Substitution confirmations are logged as a print of the text split around
+/// the match (before, match, after) and are all accepted.
 pub struct MockUI {
   pub prints_history: Vec<Print>,
 }
@@ -78,6 +82,25 @@ impl UI for MockUI {
     panic!("get_input not implemented on mock ui")
   }
 
+  fn confirm_substitution(
+    &mut self,
+    text: &str,
+    span: (usize, usize),
+  ) -> Result<Confirmation> {
+    self.prints_history.push(
+      Print{
+        text: vec![
+          text[.. span.0].to_owned(),
+          text[span.0 .. span.1].to_owned(),
+          text[span.1 ..].to_owned(),
+        ],
+        n: false,
+        l: false,
+      }
+    );
+    Ok(Confirmation::All)
+  }
+
   fn lock_ui(&mut self) -> UILock<'_> {
     UILock::new(self)
   }
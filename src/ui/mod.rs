@@ -17,6 +17,19 @@ pub use scripted_ui::ScriptedUI;
 pub mod mock_ui;
 pub mod dummy_ui;
 
+/// An answer to [`UI::confirm_substitution`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Confirmation {
+  /// Replace this match
+  Yes,
+  /// Skip this match
+  No,
+  /// Replace this and all remaining matches without asking
+  All,
+  /// Skip this and all remaining matches
+  Quit,
+}
+
 /// The UI trait used to abstract all common UI operations
 pub trait UI {
   /// A basic print for errors and other information messages
@@ -73,6 +86,19 @@ pub trait UI {
     literal: bool,
   ) -> Result<()>;
 
+  /// Ask if a match should be replaced, for `s` with the `c` flag
+  ///
+  /// * `text` is the line(s) holding the match, newline terminated.
+  /// * `span` is the byte range of the match within `text`, to highlight it.
+  ///
+  /// Default implementation accepts all replacements.
+  fn confirm_substitution(&mut self,
+    _text: &str,
+    _span: (usize, usize),
+  ) -> Result<Confirmation> {
+    Ok(Confirmation::All)
+  }
+
   /// Prepare UI before handing down stdin/out/err to child process
   ///
   /// The returned UIHandle should hold a mutable reference to its parent UI.
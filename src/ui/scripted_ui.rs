@@ -2,7 +2,7 @@
 //! The entire script is put into a vector of strings and treated as input.
 //! It optionally takes a mutable UI reference, to support printing when the script requests it.
 
-use super::{UI, UILock};
+use super::{UI, UILock, Confirmation};
 use super::Ed;
 
 use super::Result;
@@ -36,6 +36,10 @@ impl crate::error::UIErrorTrait for UnsupportedInitialData {}
 /// * If you want output from print commands put a UI to print with in
 ///   `print_ui`.
 ///   (If none given prints will be quietly ignored)
+/// * Substitution confirmations are handed to `print_ui` if given, so the user
+///   is asked even when running a macro. If none given the answers are taken
+///   from the input, as `y`, `n`, `a` or `q` on a line of their own. (Any other
+///   line answers no and running out of input answers quit.)
 
 // Things not derived here since they would require the same being implemented
 // on the UI trait, which is too extreme for me at this stage. If you have need,
@@ -115,6 +119,20 @@ impl <'a> UI for ScriptedUI<'a> {
       None => Ok(()),
     }
   }
+  fn confirm_substitution(&mut self,
+    text: &str,
+    span: (usize, usize),
+  ) -> Result<Confirmation> {
+    if let Some(ui) = &mut self.print_ui {
+      return ui.confirm_substitution(text, span);
+    }
+    Ok(match self.input.pop_front().as_ref().map(|x| x.trim()) {
+      Some("y") => Confirmation::Yes,
+      Some("a") => Confirmation::All,
+      Some("q") | None => Confirmation::Quit,
+      Some(_) => Confirmation::No,
+    })
+  }
   fn lock_ui(&mut self) -> UILock<'_> {
     match self.print_ui {
       Some(ref mut i) => i.lock_ui(),
@@ -0,0 +1,88 @@
+// Tests for '@' command
+
+use std::collections::HashMap;
+
+use add_ed::{
+  Ed,
+  EdError,
+  LineText,
+  Result,
+  plugins::Plugin,
+  ui::{UI, ScriptedUI, mock_ui::MockUI},
+  io::fake_io::FakeIO,
+};
+
+// Verify behaviour of '@' command
+//
+// - Takes optional selection, handed to the plugin after verification
+// - Requires first argument: name of a plugin
+// - Optional further arguments: handed to the plugin
+// - Errors if plugin isn't defined or if plugin errors
+// - Squashes the plugin's modifications into one snapshot, like ':'
+
+// Uppercases the selected lines, or the given lines if arguments are given
+struct Uppercase;
+impl Plugin for Uppercase {
+  fn run(
+    &self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    selection: Option<(usize, usize)>,
+    arguments: &[&str],
+  ) -> Result<()> {
+    let selection = selection.unwrap_or(ed.selection);
+    let buffer = ed.history.current_mut("should be ignored".into());
+    for line in buffer[selection.0 - 1 .. selection.1].iter_mut() {
+      line.text = LineText::new(line.text.to_uppercase()).unwrap();
+    }
+    ed.selection = selection;
+    ui.print_message(&arguments.join(","))
+  }
+}
+
+fn plugin_store() -> HashMap<&'static str, Box<dyn Plugin>> {
+  let mut store: HashMap<&str, Box<dyn Plugin>> = HashMap::new();
+  store.insert("upper", Box::new(Uppercase));
+  store
+}
+
+#[test]
+fn plugin() {
+  let mut io = FakeIO::new(
+    HashMap::from([("text".to_owned(), "a\nb\nc\n".to_owned())]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let plugins = plugin_store();
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.plugin_getter = &plugins;
+  let mut mock = MockUI{ prints_history: vec![] };
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: Some(&mut mock) };
+  // The second invocation doesn't change anything, so leaves no snapshot
+  for cmd in ["e text", "2ka", "1,2@upper x y", "1@upper"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(
+    ed.history.current().get_tagged_lines((1,3)).unwrap()
+      .collect::<Vec<_>>(),
+    vec![('\0', "A\n"), ('a', "B\n"), ('\0', "c\n")],
+    "Plugin should modify the buffer directly, keeping line tags."
+  );
+  assert_eq!(ed.selection, (1,1));
+  assert_eq!(
+    ed.history.snapshots().iter().map(|(l, _)| &l[..]).collect::<Vec<_>>(),
+    vec!["Before reading in a file (empty)", "e text", "1,2@upper x y"],
+    "Plugin modifications should be squashed into the invocation snapshot."
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "@missing"),
+    Err(EdError::PluginUndefined("missing".to_owned())),
+  );
+  drop(ui);
+  assert_eq!(
+    mock.prints_history.into_iter().skip(1).map(|p| p.text.concat())
+      .collect::<Vec<_>>(),
+    vec!["x,y", ""],
+    "Plugin should receive the given arguments."
+  );
+}
@@ -0,0 +1,77 @@
+// Tests for 'b' and 'B' command
+
+use std::collections::HashMap;
+
+use add_ed::{
+  Ed,
+  EdError,
+  ui::{ScriptedUI, mock_ui::MockUI},
+  io::fake_io::FakeIO,
+};
+
+// Verify behaviour of 'b' command
+//
+// - Doesn't allow selection
+// - `b+(<path>)` opens a new buffer, reading in the given file if any
+// - `b<nr>` switches to the given buffer
+// - `b-(<nr>)` closes the given buffer, or the active if none given
+//   - Errors if it has unsaved changes, unless 'B' is used
+// - `b` lists the open buffers
+// - Each buffer keeps its own history and file, but the clipboard is shared
+// - `q` errors if any buffer has unsaved changes
+#[test]
+fn buffers() {
+  let mut io = FakeIO::new(
+    HashMap::from([
+      ("one".to_owned(), "a\nb\n".to_owned()),
+      ("two".to_owned(), "c\nd\n".to_owned()),
+    ]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  let mut mock = MockUI{ prints_history: vec![] };
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: Some(&mut mock) };
+  for cmd in ["e one", "b+ two", "1d", "b1", "$x"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(ed.file, "one");
+  assert_eq!(
+    ed.history.current().get_lines((1,3)).unwrap().collect::<Vec<_>>(),
+    vec!["a\n","b\n","c\n"],
+    "The clipboard should be shared between buffers."
+  );
+  assert!(
+    ed.run_command(&mut ui, "b+ !missing").is_err(),
+    "Failing to read into a new buffer should error.",
+  );
+  assert_eq!(ed.buffers.count(), 2);
+  assert_eq!(ed.buffers.active(), 1);
+  assert_eq!(ed.run_command(&mut ui, "u"), Ok(false));
+  assert_eq!(
+    ed.run_command(&mut ui, "q"),
+    Err(EdError::BufferUnsaved(2)),
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "b-2"),
+    Err(EdError::BufferUnsaved(2)),
+  );
+  ed.run_command(&mut ui, "b").unwrap();
+  assert_eq!(ed.run_command(&mut ui, "B-2"), Ok(false));
+  assert_eq!(
+    ed.run_command(&mut ui, "b-"),
+    Err(EdError::BufferLast),
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "b2"),
+    Err(EdError::BufferNotFound("2".to_owned())),
+  );
+  assert_eq!(ed.run_command(&mut ui, "q"), Ok(true));
+  drop(ui);
+  assert_eq!(
+    mock.prints_history.into_iter().map(|p| p.text.concat())
+      .collect::<Vec<_>>()
+      .last().unwrap(),
+    "> 1: one \n  2: two (unsaved)",
+  );
+}
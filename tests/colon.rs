@@ -11,6 +11,7 @@ use add_ed::{
   macros::{
     Macro,
     NrArguments,
+    MacroSnapshottingMode,
   },
 };
 
@@ -31,6 +32,12 @@ fn create_macro_store() -> std::collections::HashMap<&'static str, Macro> {
   store.insert("append_word", Macro::new(",a\n$1\n.").nr_arguments(NrArguments::Exactly(1)));
   store.insert("append_words", Macro::new(",a\n$0\n."));
   store.insert("recursion", Macro::new(":recursion").nr_arguments(NrArguments::Exactly(0)));
+  store.insert("revert", Macro::new(",d\na\nx\n.")
+    .snapshotting_mode(MacroSnapshottingMode::RevertMutation)
+  );
+  store.insert("expose", Macro::new("1d\n$d")
+    .snapshotting_mode(MacroSnapshottingMode::ExposeModifications)
+  );
   store
 }
 
@@ -108,3 +115,95 @@ fn macro_recursion() {
     expected_error: EdError::InfiniteRecursion,
   }.run();
 }
+
+// Verify that macros set to revert mutation leave no trace of their
+// modifications, neither in buffer, selection nor history
+#[test]
+fn macro_revert_mutation() {
+  MacroTest{
+    init_buffer: vec!["a","b","c"],
+    init_clipboard: vec!["d"],
+    macro_store: create_macro_store(),
+    macro_invocation: "2:revert",
+    expected_buffer: vec!["a","b","c"],
+    expected_buffer_saved: true,
+    expected_selection: (2,2),
+    expected_clipboard: vec!["d"],
+    expected_history_tags: vec![],
+  }.run();
+}
+
+// Verify that macros set to expose modifications create a snapshot per
+// modifying command in the macro
+#[test]
+fn macro_expose_modifications() {
+  MacroTest{
+    init_buffer: vec!["a","b","c"],
+    init_clipboard: vec![],
+    macro_store: create_macro_store(),
+    macro_invocation: ":expose",
+    expected_buffer: vec!["b"],
+    expected_buffer_saved: false,
+    expected_selection: (1,1),
+    expected_clipboard: vec!["c"],
+    expected_history_tags: vec!["1d","$d"],
+  }.run();
+}
+
+// Verify that macros can be configured to continue past errors
+//
+// - `AbortExceptNoMatch` ignores failing to find matches, but not other errors
+// - `Ignore` ignores all errors
+// - Ignored errors are printed after the macro finishes
+#[test]
+fn macro_error_handling() {
+  use std::collections::HashMap;
+  use add_ed::{Ed, ui::{ScriptedUI, mock_ui::MockUI}};
+  use add_ed::macros::MacroErrorHandling;
+  use add_ed::io::fake_io::FakeIO;
+  let mut io = FakeIO::new(
+    HashMap::from([("text".to_owned(), "a\nb\nc\n".to_owned())]),
+    HashMap::new(),
+  );
+  let mut macros = HashMap::new();
+  macros.insert("no_match", Macro::new("/x/d\n1d")
+    .error_handling(MacroErrorHandling::AbortExceptNoMatch)
+  );
+  macros.insert("undefined", Macro::new("O\n1d")
+    .error_handling(MacroErrorHandling::AbortExceptNoMatch)
+  );
+  macros.insert("ignore", Macro::new("O\n1d")
+    .error_handling(MacroErrorHandling::Ignore)
+  );
+  let mut ed = Ed::new(&mut io, &macros);
+  let mut mock = MockUI{ prints_history: vec![] };
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: Some(&mut mock) };
+  ed.run_command(&mut ui, "e text").unwrap();
+  ed.run_command(&mut ui, ":no_match").unwrap();
+  assert_eq!(
+    ed.run_command(&mut ui, ":undefined"),
+    Err(EdError::CommandUndefined('O')),
+  );
+  ed.run_command(&mut ui, ":ignore").unwrap();
+  assert_eq!(
+    ed.history.current().get_lines((1,1)).unwrap().collect::<Vec<_>>(),
+    vec!["c\n"],
+    "Commands after ignored errors should still run."
+  );
+  drop(ui);
+  assert_eq!(
+    mock.prints_history.into_iter().skip(1).map(|p| p.text.concat())
+      .collect::<Vec<_>>(),
+    vec![
+      format!(
+        "Ignored 1 error(s) during macro execution:\n{}",
+        EdError::RegexNoMatch("x".to_owned()),
+      ),
+      format!(
+        "Ignored 1 error(s) during macro execution:\n{}",
+        EdError::CommandUndefined('O'),
+      ),
+    ],
+    "Prints (left) didn't match expectations (right)."
+  );
+}
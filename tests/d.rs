@@ -123,3 +123,28 @@ fn delete_endofbuffer() {
     expected_history_tags: vec!["2,3d"],
   }.run();
 }
+
+// Test cutting into a named register, leaving the clipboard untouched
+#[test]
+fn cut_register() {
+  BasicTest{
+    init_buffer: vec!["a","b","c"],
+    init_clipboard: vec!["dummy"],
+    command_input: vec!["1d\"a","$x\"a"],
+    expected_buffer: vec!["b","c","a"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec!["dummy"],
+    expected_selection: (3,3),
+    expected_history_tags: vec!["1d\"a","$x\"a"],
+  }.run()
+}
+
+// Test giving an invalid register name
+#[test]
+fn cut_invalid_register() {
+  ErrorTest{
+    init_buffer: vec!["a","b","c"],
+    command_input: vec!["1d\"!"],
+    expected_error: EdError::RegisterInvalid("\"!".to_owned()),
+  }.run()
+}
@@ -24,13 +24,13 @@ use shared::fake_io::{
 
 // Function to set up the "filesystem" for these tests
 fn test_io() -> FakeIO {
-  FakeIO {
-    fake_fs: HashMap::from([
+  FakeIO::new(
+    HashMap::from([
       ("text".to_owned(), "file\ndata\nin\nfile\n".to_owned()),
       ("numbers".to_owned(), "4\n5\n2\n1\n".to_owned()),
       ("not_terminated".to_owned(), "test\ndata".to_owned()),
     ]),
-    fake_shell: HashMap::from([
+    HashMap::from([
       (
         ShellCommand{
           command:"echo hi".to_owned(),
@@ -46,7 +46,7 @@ fn test_io() -> FakeIO {
         "1\n2\n4\n5\n".to_owned(),
       ),
     ]),
-  }
+  )
 }
 
 // No selection and no path, verify defaults
@@ -202,3 +202,35 @@ fn force_edit_unsaved() {
     expected_filepath: "text",
   }.run();
 }
+
+// Verify that 'e' resumes undo history persisted by 'w' when enabled
+#[cfg(feature = "serde")]
+#[test]
+fn edit_persisted_history() {
+  use add_ed::{Ed, ui::ScriptedUI};
+  let mut io = test_io();
+  let macros = HashMap::new();
+  // Edit and save the file, persisting the history
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.persist_history = true;
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  for cmd in ["e text", "1d", "w"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  // Then open it again in a new editor and undo the previous edit
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.persist_history = true;
+  for cmd in ["e text", "u"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(
+    ed.history.snapshots().iter().map(|(l, _)| &l[..]).collect::<Vec<_>>(),
+    vec!["Before reading in a file (empty)", "e text", "1d"],
+    "History labels after reopening (left) didn't match expectations (right)."
+  );
+  assert_eq!(
+    ed.history.current().get_lines((1,4)).unwrap().collect::<Vec<_>>(),
+    vec!["file\n","data\n","in\n","file\n"],
+    "Undoing after reopening didn't restore the state before the edit."
+  );
+}
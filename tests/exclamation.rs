@@ -20,12 +20,12 @@ use shared::fake_io::{
 
 // Function to set up the "filesystem" for these tests
 fn test_io() -> FakeIO {
-  FakeIO {
-    fake_fs: HashMap::from([
+  FakeIO::new(
+    HashMap::from([
       ("text".to_owned(), "file\ndata\nin\nfile\n".to_owned()),
       ("numbers".to_owned(), "4\n5\n2\n1\n".to_owned()),
     ]),
-    fake_shell: HashMap::from([
+    HashMap::from([
       (
         ShellCommand{
           command:"echo hi".to_owned(),
@@ -41,7 +41,7 @@ fn test_io() -> FakeIO {
         "1\n2\n4\n5\n".to_owned(),
       ),
     ]),
-  }
+  )
 }
 
 // No selection, just run command
@@ -0,0 +1,84 @@
+// Tests for the command extender hook
+
+use std::collections::HashMap;
+
+use add_ed::{
+  Ed,
+  EdError,
+  LineText,
+  Result,
+  extender::*,
+  ui::{UI, ScriptedUI, mock_ui::{MockUI, Print}},
+  io::fake_io::FakeIO,
+};
+
+// Verify behaviour of the command extender
+//
+// - Receives commands not defined in add-ed, with selection and tail
+// - Printing flags set by the extender are applied after the command
+// - Still errors with CommandUndefined if the extender doesn't define it
+
+// Defines 'o', which reverses the text of the selected lines
+struct Reverser;
+impl CommandExtender for Reverser {
+  fn run_command(
+    &self,
+    ed: &mut Ed<'_>,
+    _ui: &mut dyn UI,
+    pflags: &mut PrintingFlags,
+    selection: Option<Sel<'_>>,
+    command: char,
+    tail: &str,
+  ) -> Result<bool> {
+    if command != 'o' { return Err(EdError::CommandUndefined(command)); }
+    let sel = interpret_selection(ed, selection, ed.selection)?;
+    ed.history.current().verify_selection(sel)?;
+    let mut flags = parse_flags(tail, "pnl")?;
+    pflags.p = flags.remove(&'p').unwrap();
+    pflags.n = flags.remove(&'n').unwrap();
+    pflags.l = flags.remove(&'l').unwrap();
+    let buffer = ed.history.current_mut(format!("{}o{}", sel.0, tail));
+    for line in buffer[sel.0 - 1 .. sel.1].iter_mut() {
+      let reversed: String = line.text.trim_end_matches('\n').chars().rev()
+        .collect();
+      line.text = LineText::new(reversed + "\n").unwrap();
+    }
+    ed.selection = sel;
+    Ok(false)
+  }
+}
+
+#[test]
+fn command_extender() {
+  let mut io = FakeIO::new(
+    HashMap::from([("text".to_owned(), "abc\ndef\n".to_owned())]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.command_extender = &Reverser;
+  let mut mock = MockUI{ prints_history: vec![] };
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: Some(&mut mock) };
+  ed.run_command(&mut ui, "e text").unwrap();
+  ed.run_command(&mut ui, "2op").unwrap();
+  assert_eq!(
+    ed.history.current().get_lines((1,2)).unwrap().collect::<Vec<_>>(),
+    vec!["abc\n","fed\n"],
+    "Extension command should modify the selected lines."
+  );
+  assert_eq!(ed.selection, (2,2));
+  assert_eq!(
+    ed.run_command(&mut ui, "O"),
+    Err(EdError::CommandUndefined('O')),
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "ox"),
+    Err(EdError::FlagUndefined('x')),
+  );
+  drop(ui);
+  assert_eq!(
+    mock.prints_history[1],
+    Print{ text: vec!["fed\n".to_owned()], n: false, l: false },
+    "Printing flags set by the extender should be applied."
+  );
+}
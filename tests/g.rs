@@ -2,7 +2,10 @@
 // 'g' first, 'G' after
 
 mod shared;
-use shared::fixtures::PrintTest;
+use shared::fixtures::{
+  BasicTest,
+  PrintTest,
+};
 use shared::mock_ui::Print;
 
 // Verify behaviour of 'g'
@@ -56,6 +59,69 @@ fn global_grep_defaultcommand() {
   }.run();
 }
 
+// Test regex flags, given in braces before the commands
+#[test]
+fn global_regex_flags() {
+  let buffer = vec![
+    "Hello",
+    "1",
+    "hello",
+  ];
+  PrintTest{
+    init_buffer: buffer.clone(),
+    init_clipboard: vec![],
+    command_input: vec![r",g/hello/{I}p/"],
+    expected_buffer: buffer,
+    expected_buffer_saved: true,
+    expected_selection: (3,3),
+    expected_clipboard: vec![],
+    expected_prints: vec![
+      Print{
+        text: vec!["Hello\n".to_string(),],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["hello\n".to_string(),],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec![],
+  }.run();
+}
+
+// Test that commands made of flag characters are still run as commands, here
+// repeating the previous substitution, and that braced flags go before them
+#[test]
+fn global_flag_like_commands() {
+  BasicTest{
+    init_buffer: vec!["foo","bar","Foo"],
+    init_clipboard: vec![],
+    command_input: vec!["1s/o/0/", ",g/o$/s/", ",g/f/{I}s_0_1_/"],
+    expected_buffer: vec!["f10","bar","F1o"],
+    expected_buffer_saved: false,
+    expected_selection: (3,3),
+    expected_clipboard: vec!["F0o"],
+    expected_history_tags: vec!["1s/o/0/", ",g/o$/s/", ",g/f/{I}s_0_1_/"],
+  }.run();
+}
+
+// Test that an empty regex reuses the previous one
+#[test]
+fn global_reuse_pattern() {
+  BasicTest{
+    init_buffer: vec!["a","1","b","2"],
+    init_clipboard: vec![],
+    command_input: vec![r"/\d/#",",g//d/"],
+    expected_buffer: vec!["a","b"],
+    expected_buffer_saved: false,
+    expected_selection: (2,2),
+    expected_clipboard: vec!["2"],
+    expected_history_tags: vec![",g//d/"],
+  }.run();
+}
+
 // Test a slightly fancier multiline invocation
 #[test]
 fn global_grep_and_delete() {
@@ -10,6 +10,7 @@ use shared::dummy_io::DummyIO;
 use add_ed::{
   ui::ScriptedUI,
   Ed,
+  EdError,
 };
 
 // Verify behaviour of 'p' command
@@ -50,6 +51,88 @@ fn print() {
   }.run()
 }
 
+// Test regex flags on pattern indices, given in braces
+#[test]
+fn print_pattern_regex_flags() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","B"],
+    init_clipboard: vec![],
+    command_input: vec!["1#","/B/{I}p","$#","?A?{I}p"],
+    expected_selection: (1,1),
+    expected_buffer: vec!["a","b","c","B"],
+    expected_buffer_saved: true,
+    expected_clipboard: vec![],
+    expected_prints: vec![
+      Print{
+        text: vec!["b\n".to_string()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["a\n".to_string()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec![],
+  }.run()
+}
+
+// Pattern indices wrap around the buffer, reporting that they did
+#[test]
+fn print_pattern_wrap() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","b"],
+    init_clipboard: vec![],
+    command_input: vec!["3#","/a/p","?c?p"],
+    expected_selection: (3,3),
+    expected_buffer: vec!["a","b","c","b"],
+    expected_buffer_saved: true,
+    expected_clipboard: vec![],
+    expected_prints: vec![
+      Print{
+        text: vec!["a\n".to_string()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["c\n".to_string()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec![],
+  }.run()
+}
+#[test]
+fn print_pattern_wrap_setting() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ui = ScriptedUI{
+    print_ui: None,
+    input: ["a\n","b\n","c\n",".\n"].iter().map(|x| x.to_string())
+      .collect(),
+  };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.run_command(&mut ui, "a").unwrap();
+  ed.run_command(&mut ui, "2#").unwrap();
+  ed.run_command(&mut ui, "/c/#").unwrap();
+  assert!(!ed.search_wrapped.get(), "Search shouldn't report wrapping.");
+  ed.run_command(&mut ui, "/b/#").unwrap();
+  assert_eq!(ed.selection, (2,2));
+  assert!(ed.search_wrapped.get(), "Search should report wrapping.");
+  ed.run_command(&mut ui, "#").unwrap();
+  assert!(!ed.search_wrapped.get(), "Report should reset between commands.");
+  ed.wrap_search = false;
+  assert_eq!(
+    ed.run_command(&mut ui, "?c?#"),
+    Err(EdError::RegexNoMatch("c".to_owned())),
+  );
+}
+
 // Test flag handling and using default selection
 #[test]
 fn print_literal_numbered_noselection() {
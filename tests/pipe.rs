@@ -24,12 +24,12 @@ use shared::fake_io::{
 
 // Function to set up the "filesystem" for these tests
 fn test_io() -> FakeIO {
-  FakeIO {
-    fake_fs: HashMap::from([
+  FakeIO::new(
+    HashMap::from([
       ("text".to_owned(), "file\ndata\nin\nfile\n".to_owned()),
       ("numbers".to_owned(), "4\n5\n2\n1\n".to_owned()),
     ]),
-    fake_shell: HashMap::from([
+    HashMap::from([
       (
         ShellCommand{
           command:"echo hi".to_owned(),
@@ -45,7 +45,7 @@ fn test_io() -> FakeIO {
         "1\n2\n4\n5\n".to_owned(),
       ),
     ]),
-  }
+  )
 }
 
 // To be moved into pipe.rs
@@ -66,3 +66,42 @@ fn shell_pipe() {
     expected_filepath: "numbers",
   }.run();
 }
+
+// Shell commands are given the editor's state
+#[test]
+fn shell_context() {
+  use add_ed::{Ed, ui::ScriptedUI, io::ShellContext, macros::Macro};
+  let mut io = test_io();
+  io.fake_shell.insert(
+    ShellCommand{
+      command: "sort -n".to_owned(),
+      input: "5\n2\n".to_owned(),
+    },
+    "2\n5\n".to_owned(),
+  );
+  let macros = HashMap::new();
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  let mut ed = Ed::new(&mut io, &macros);
+  for cmd in ["e numbers", "2,3|sort -n", "!echo hi", "g/5/!echo hi/", "1"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  // A macro run directly is scripted as well
+  ed.run_macro(&mut ui, &Macro::new("!echo hi"), &[] as &[&str]).unwrap();
+  drop(ed);
+  let context = |selection, prev: &str, scripted| ShellContext{
+    file: "numbers".to_owned(),
+    selection,
+    buffer_len: 4,
+    prev_shell_command: prev.to_owned(),
+    scripted,
+  };
+  assert_eq!(
+    io.shell_contexts(),
+    vec![
+      ("sort -n".to_owned(), context((2,3), "", false)),
+      ("echo hi".to_owned(), context((2,3), "sort -n", false)),
+      ("echo hi".to_owned(), context((3,3), "echo hi", true)),
+      ("echo hi".to_owned(), context((1,1), "echo hi", true)),
+    ],
+  );
+}
@@ -0,0 +1,91 @@
+// Tests for selecting the regex engine
+
+use std::collections::HashMap;
+
+use add_ed::{
+  Ed,
+  Result,
+  RegexFlags,
+  regex_engine::*,
+  ui::ScriptedUI,
+  io::fake_io::FakeIO,
+};
+
+// Verify behaviour of the regex engine selection
+//
+// - Pattern indices, 's' and 'g' all compile their regex via the engine
+// - The replacement template is expanded by the engine
+
+// Matches the pattern literally, to tell it apart from the default engine
+struct LiteralEngine;
+struct Literal(String);
+impl RegexEngine for LiteralEngine {
+  fn compile(&self,
+    pattern: &str,
+    _flags: RegexFlags,
+  ) -> Result<Box<dyn CompiledRegex>> {
+    Ok(Box::new(Literal(pattern.to_owned())))
+  }
+}
+impl CompiledRegex for Literal {
+  fn is_match(&self, text: &str) -> Result<bool> {
+    Ok(text.contains(&self.0))
+  }
+  fn find_iter<'a>(&'a self,
+    text: &'a str,
+    replacement: Option<&'a str>,
+  ) -> Matches<'a> {
+    Box::new(text.match_indices(&self.0).map(move |(start, found)| {
+      Ok(RegexMatch{
+        start,
+        end: start + found.len(),
+        replacement: replacement.map(|r| r.to_owned()),
+      })
+    }))
+  }
+}
+
+#[test]
+fn custom_engine() {
+  let mut io = FakeIO::new(
+    HashMap::from([
+      ("file".to_owned(), "a.c\nabc\na.c\n".to_owned()),
+    ]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.regex_engine = &LiteralEngine;
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  for cmd in ["e file", "1#", "/a.c/d", ",s/./-$1/g"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(
+    ed.history.current().get_lines((1,2)).unwrap().collect::<Vec<_>>(),
+    vec!["a-$1c\n","abc\n"],
+  );
+  assert_eq!(ed.history.current().len(), 2);
+}
+
+// The backtracking engine supports lookaround
+#[cfg(feature = "fancy_regex")]
+#[test]
+fn fancy_engine() {
+  let mut io = FakeIO::new(
+    HashMap::from([
+      ("file".to_owned(), "foo(a)\nfoo b\nFOO\n".to_owned()),
+    ]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.regex_engine = &FancyRegex;
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  for cmd in ["e file", r",s/(fo)o(?!\()/${1}x/gI"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(
+    ed.history.current().get_lines((1,3)).unwrap().collect::<Vec<_>>(),
+    vec!["foo(a)\n","fox b\n","FOx\n"],
+  );
+}
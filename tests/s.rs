@@ -3,7 +3,9 @@
 mod shared;
 use shared::fixtures::{
   BasicTest,
+  ErrorTest,
 };
+use add_ed::EdError;
 
 // Verify behaviour of 's' command
 //
@@ -13,7 +15,7 @@ use shared::fixtures::{
 // - Takes no or 3 arguments separated by first character after 's'
 //   - First is match pattern
 //   - Second is the substitution pattern
-//   - Third is command flags, any of "gpnl" (TODO: add 'COUNT' support)
+//   - Third is command flags, any of "gpnlIxs" (TODO: add 'COUNT' support)
 //   - (The separator is escapeable with '\')
 // - Selection after is the resulting size of the initial selection after
 //   substitution.
@@ -65,6 +67,22 @@ fn substitute_global_deleteline() {
   }.run()
 }
 
+// Test substituting on a line selected by a pattern index, whose flag
+// characters aren't taken as regex flags
+#[test]
+fn substitute_pattern_index() {
+  BasicTest{
+    init_buffer: vec!["a","foo","b"],
+    init_clipboard: vec![],
+    command_input: vec!["/foo/s/o/0/"],
+    expected_buffer: vec!["a","f0o","b"],
+    expected_buffer_saved: false,
+    expected_selection: (2,2),
+    expected_clipboard: vec!["foo"],
+    expected_history_tags: vec!["/foo/s/o/0/"],
+  }.run()
+}
+
 // Test multiline pattern matching
 #[test]
 fn substitute_multiline() {
@@ -94,3 +112,212 @@ fn substitute_removenewline() {
     expected_history_tags: vec![",2s_\n__g"],
   }.run()
 }
+
+// Test regex flags, case insensitive and verbose (ignoring whitespace) here,
+// which are also reused when repeating the substitution
+#[test]
+fn substitute_regex_flags() {
+  BasicTest{
+    init_buffer: vec!["Hello","hello","HELLO"],
+    init_clipboard: vec![],
+    command_input: vec![",2s/h e l l o/hi/gIx", "3s"],
+    expected_buffer: vec!["hi","hi","hi"],
+    expected_buffer_saved: false,
+    expected_selection: (3,3),
+    expected_clipboard: vec!["HELLO"],
+    expected_history_tags: vec![",2s/h e l l o/hi/gIx", "3s"],
+  }.run()
+}
+
+// Test GNU mode, with match number, '&', '\1' and '%' as replacement
+#[test]
+fn substitute_gnu() {
+  use std::collections::HashMap;
+  use add_ed::{
+    Ed,
+    ui::ScriptedUI,
+    io::fake_io::FakeIO,
+  };
+  let mut io = FakeIO::new(
+    HashMap::from([
+      ("file".to_owned(), "a a a\nb-c\nd d d\n".to_owned()),
+    ]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.gnu_substitute = true;
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  for cmd in ["e file", r"2s/(\w)-(\w)/\2-\1/", r"1s/a/<&$>/2", "3s/d/%/2g"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(
+    ed.history.current().get_lines((1,3)).unwrap().collect::<Vec<_>>(),
+    vec!["a <a$> a\n","c-b\n","d <d$> <d$>\n"],
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "1s/a/b/0"),
+    Err(EdError::SubstituteOccurrenceInvalid("0".to_owned())),
+  );
+  // Digits in more than one place are rejected, rather than joined
+  assert_eq!(
+    ed.run_command(&mut ui, "1s/a/b/2g3"),
+    Err(EdError::SubstituteOccurrenceInvalid("2g3".to_owned())),
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "1s/a/b/g1p0"),
+    Err(EdError::SubstituteOccurrenceInvalid("g1p0".to_owned())),
+  );
+}
+
+// Test that an empty regex reuses the previous one, here from an index
+#[test]
+fn substitute_reuse_pattern() {
+  BasicTest{
+    init_buffer: vec!["a","b","ab"],
+    init_clipboard: vec![],
+    command_input: vec!["/b/{I}s//c/"],
+    expected_buffer: vec!["a","c","ab"],
+    expected_buffer_saved: false,
+    expected_selection: (2,2),
+    expected_clipboard: vec!["b"],
+    expected_history_tags: vec!["/b/{I}s//c/"],
+  }.run();
+  ErrorTest{
+    init_buffer: vec!["a","b","ab"],
+    command_input: vec!["s//c/"],
+    expected_error: EdError::DefaultPatternUnset,
+  }.run()
+}
+
+// Test confirming each replacement, answering from a script and via a UI
+#[test]
+fn substitute_confirm() {
+  use std::collections::HashMap;
+  use add_ed::{
+    Ed,
+    ui::{ScriptedUI, mock_ui::{MockUI, Print}},
+    io::fake_io::FakeIO,
+  };
+  let mut io = FakeIO::new(
+    HashMap::from([
+      ("file".to_owned(), "a a\na\n".to_owned()),
+    ]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  // Without print_ui answers are taken from the input, running out means quit
+  let mut ui = ScriptedUI{
+    input: vec!["n\n".to_owned(), "y\n".to_owned()].into(),
+    print_ui: None,
+  };
+  ed.run_command(&mut ui, "e file").unwrap();
+  ed.run_command(&mut ui, ",s/a/b/gc").unwrap();
+  assert_eq!(
+    ed.history.current().get_lines((1,2)).unwrap().collect::<Vec<_>>(),
+    vec!["a b\n","a\n"],
+  );
+  // Declining all changes nothing
+  let mut ui = ScriptedUI{ input: vec!["q\n".to_owned()].into(), print_ui: None };
+  ed.run_command(&mut ui, "2s/a/b/c").unwrap();
+  assert_eq!(ed.history.snapshots().len(), 3);
+  // The mock UI is shown each match and accepts all
+  let mut mock = MockUI{ prints_history: vec![] };
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: Some(&mut mock) };
+  ed.run_command(&mut ui, "s").unwrap();
+  assert_eq!(
+    ed.history.current().get_lines((1,2)).unwrap().collect::<Vec<_>>(),
+    vec!["a b\n","b\n"],
+  );
+  assert_eq!(
+    mock.prints_history,
+    vec![Print{
+      text: vec!["".to_owned(), "a".to_owned(), "\n".to_owned()],
+      n: false,
+      l: false,
+    }],
+  );
+}
+
+// Matches and substitutions can be queried without changing state
+#[test]
+fn substitute_preview() {
+  use std::collections::HashMap;
+  use add_ed::{
+    Ed,
+    LineMatch,
+    RegexFlags,
+    Substitution,
+    ui::ScriptedUI,
+    io::fake_io::FakeIO,
+  };
+  let mut io = FakeIO::new(
+    HashMap::from([
+      ("file".to_owned(), "a a\nb\nab\n".to_owned()),
+    ]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  ed.run_command(&mut ui, "e file").unwrap();
+  let snapshots = ed.history.snapshots().len();
+  assert_eq!(
+    ed.find_matches("a", RegexFlags::default(), (1,3)).unwrap(),
+    vec![
+      LineMatch{ line: 1, start: 0, end: 1 },
+      LineMatch{ line: 1, start: 2, end: 3 },
+      LineMatch{ line: 3, start: 0, end: 1 },
+    ],
+  );
+  // Matches spanning lines are split per line
+  assert_eq!(
+    ed.find_matches("a\nb", RegexFlags::default(), (1,2)).unwrap(),
+    vec![
+      LineMatch{ line: 1, start: 2, end: 4 },
+      LineMatch{ line: 2, start: 0, end: 1 },
+    ],
+  );
+  let s = Substitution{
+    pattern: "a".to_owned(),
+    regex_flags: RegexFlags::default(),
+    substitute: "c\\n".to_owned(),
+    global: true,
+    occurrence: 1,
+    confirm: true,
+    p: false,
+    n: false,
+    l: false,
+  };
+  assert_eq!(
+    ed.preview_substitution(&s, (1,2)).unwrap(),
+    vec!["c\n"," c\n","\n","b\n"],
+  );
+  assert_eq!(
+    ed.preview_substitution(&s, (2,2)),
+    Err(EdError::RegexNoMatch("a".to_owned())),
+  );
+  // Nothing was changed by the previews
+  assert_eq!(ed.history.snapshots().len(), snapshots);
+  assert_eq!(
+    ed.history.current().get_lines((1,3)).unwrap().collect::<Vec<_>>(),
+    vec!["a a\n","b\n","ab\n"],
+  );
+  // An empty pattern and '%' are resolved from the previous 's'
+  ed.gnu_substitute = true;
+  let s = Substitution{
+    pattern: String::new(),
+    substitute: "%".to_owned(),
+    ..s
+  };
+  assert_eq!(
+    ed.preview_substitution(&s, (3,3)),
+    Err(EdError::DefaultPatternUnset),
+  );
+  ed.run_command(&mut ui, "2s/b/x/").unwrap();
+  assert_eq!(
+    ed.preview_substitution(&s, (3,3)).unwrap(),
+    vec!["ax\n"],
+  );
+}
@@ -33,10 +33,7 @@ pub fn inner_fixture(
   expected_prints: Vec<Print>,
 ) {
   // Instantiate dummy IO
-  let mut io = FakeIO{
-    fake_fs: HashMap::new(),
-    fake_shell: HashMap::new(),
-  };
+  let mut io = FakeIO::new(HashMap::new(), HashMap::new());
   // Apply given or default to no macros
   let macros = init_macros.unwrap_or(HashMap::new());
   // Create ed state and init ed.buffer
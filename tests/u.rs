@@ -127,3 +127,175 @@ fn undo_tag_move() {
     expected_history_tags: vec!["2m"],
   }.run()
 }
+
+// Verify behaviour of 'u' and 'U' in the tree history mode
+//
+// - Modifying after undo creates a new branch, instead of a revert snapshot
+// - `u<` and `u>` switch between the ends of the branches
+// - `U` prints the current branch and `Ub` the ends of all branches
+#[test]
+fn undo_tree() {
+  use std::collections::HashMap;
+  use add_ed::{Ed, HistoryMode, ui::{ScriptedUI, mock_ui::MockUI}};
+  use add_ed::io::fake_io::FakeIO;
+  let mut io = FakeIO::new(
+    HashMap::from([("text".to_owned(), "a\nb\nc\n".to_owned())]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros).history_mode(HistoryMode::Tree);
+  let mut mock = MockUI{ prints_history: vec![] };
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: Some(&mut mock) };
+  for cmd in ["e text", "1d", "u", "2d", "Ub", "u<", "U"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(
+    ed.history.snapshots().iter().map(|(l, _)| &l[..]).collect::<Vec<_>>(),
+    vec!["Before reading in a file (empty)", "e text", "1d", "2d"],
+    "History labels (left) didn't match expectations (right)."
+  );
+  assert_eq!(
+    ed.history.current().get_lines((1,2)).unwrap().collect::<Vec<_>>(),
+    vec!["b\n","c\n"],
+    "Switching branch didn't give the state at the end of the older branch."
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "u<"),
+    Err(add_ed::error::EdError::UndoBranchTooFar{relative_limit: 0}),
+  );
+  drop(ui);
+  assert_eq!(
+    mock.prints_history.into_iter().map(|p| p.text.concat())
+      .collect::<Vec<_>>(),
+    vec![
+      "Read 6 bytes from path `text`",
+      "Undid 1 operation(s) to right after e text.",
+      "  1d \n> 2d ",
+      "Switched 1 branch(es) to right after 1d.",
+      "  Before reading in a file (empty) \n  e text (saved)\n> 1d ",
+    ],
+    "Prints (left) didn't match expectations (right)."
+  );
+}
+
+// Verify behaviour of absolute and label searching 'u'
+//
+// - `u*N` moves to absolute index N in history
+// - `u?re?` moves to the nearest earlier snapshot with a label matching re
+// - `u/re/` moves to the nearest later snapshot with a label matching re
+// - Errors if no snapshot matches or index is too big
+#[test]
+fn undo_absolute_and_search() {
+  use std::collections::HashMap;
+  use add_ed::{Ed, ui::ScriptedUI, error::EdError};
+  use add_ed::io::fake_io::FakeIO;
+  let mut io = FakeIO::new(
+    HashMap::from([("text".to_owned(), "a\nb\nc\n".to_owned())]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  for cmd in ["e text", "1d", "1d", "u*0"] {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(ed.history.viewed_i(), 0);
+  ed.run_command(&mut ui, "u/d$").unwrap();
+  assert_eq!(ed.history.viewed_i(), 2);
+  ed.run_command(&mut ui, "u/").unwrap();
+  assert_eq!(ed.history.viewed_i(), 3);
+  ed.run_command(&mut ui, "u?^e?").unwrap();
+  assert_eq!(ed.history.viewed_i(), 1);
+  assert_eq!(
+    ed.run_command(&mut ui, "u?^e?"),
+    Err(EdError::RegexNoMatch("^e".to_owned())),
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "u*4"),
+    Err(EdError::UndoIndexTooBig{
+      index: 4,
+      history_len: 4,
+      relative_redo_limit: 2,
+    }),
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "u*a"),
+    Err(EdError::UndoIndexNotInt("a".to_owned())),
+  );
+}
+
+// Verify behaviour of 'U' command
+//
+// - Prints the history around the given index, or viewed snapshot if none
+// - `a` prints absolute indices, `A` the whole branch
+// - `$-N` gives an index relative to the end of history
+// - `c` clears history, before given index if any
+#[test]
+fn manage_history() {
+  use std::collections::HashMap;
+  use add_ed::{Ed, ui::{ScriptedUI, mock_ui::MockUI}, error::EdError};
+  use add_ed::io::fake_io::FakeIO;
+  let mut io = FakeIO::new(
+    HashMap::from([(
+      "text".to_owned(),
+      "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n".to_owned(),
+    )]),
+    HashMap::new(),
+  );
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  let mut mock = MockUI{ prints_history: vec![] };
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: Some(&mut mock) };
+  let mut commands = vec!["e text"];
+  commands.extend(["1d"; 11]);
+  commands.extend(["u2", "Ua$-11", "UA", "Uc2", "Ua"]);
+  for cmd in commands {
+    ed.run_command(&mut ui, cmd).unwrap();
+  }
+  assert_eq!(
+    ed.run_command(&mut ui, "Uc"),
+    Ok(false),
+  );
+  assert_eq!(
+    ed.run_command(&mut ui, "Uc5"),
+    Err(EdError::UndoIndexTooBig{
+      index: 5,
+      history_len: 1,
+      relative_redo_limit: 0,
+    }),
+  );
+  assert_eq!(ed.history.len(), 1);
+  assert_eq!(
+    ed.history.current().get_lines((1,3)).unwrap().collect::<Vec<_>>(),
+    vec!["j\n","k\n","l\n"],
+    "Clearing history shouldn't change the viewed buffer state."
+  );
+  drop(ui);
+  let prints: Vec<String> = mock.prints_history.into_iter()
+    .map(|p| p.text.concat())
+    .collect()
+  ;
+  let listing = |lines: Vec<String>| lines.join("\n");
+  assert_eq!(
+    &prints[2..],
+    &[
+      listing((0 .. 10).map(|i| match i {
+        0 => "  0: Before reading in a file (empty) ".to_owned(),
+        1 => "  1: e text (saved)".to_owned(),
+        i => format!("  {}: 1d ", i),
+      }).collect()),
+      listing((0 .. 13).map(|i| match i {
+        0 => "  Before reading in a file (empty) ".to_owned(),
+        1 => "  e text (saved)".to_owned(),
+        10 => "> 1d ".to_owned(),
+        _ => "  1d ".to_owned(),
+      }).collect()),
+      "Removed 2 snapshot(s) from history.".to_owned(),
+      listing((0 .. 10).map(|i| if i == 8 { format!("> {}: 1d ", i) } else {
+        format!("  {}: 1d ", i)
+      }).collect()),
+      "Removed 10 snapshot(s) from history.".to_owned(),
+    ],
+    "Prints (left) didn't match expectations (right)."
+  );
+}
@@ -26,12 +26,14 @@ use shared::fake_io::{
 
 // Function to set up the "filesystem" for these tests
 fn test_io() -> FakeIO {
-  FakeIO {
-    fake_fs: HashMap::from([
+  FakeIO::new(
+    HashMap::from([
       ("text".to_owned(), "file\ndata\nin\nfile\n".to_owned()),
       ("numbers".to_owned(), "4\n5\n2\n1\n".to_owned()),
+      ("unterminated".to_owned(), "a\nb".to_owned()),
+      ("crlf".to_owned(), "a\r\nb\r\n".to_owned()),
     ]),
-    fake_shell: HashMap::from([
+    HashMap::from([
       (
         ShellCommand{
           command:"echo hi".to_owned(),
@@ -47,7 +49,7 @@ fn test_io() -> FakeIO {
         "1\n2\n4\n5\n".to_owned(),
       ),
     ]),
-  }
+  )
 }
 
 // No selection and no path, verify defaults
@@ -92,6 +94,92 @@ fn write_fully_specified() {
   }.run();
 }
 
+// A file read without a final newline is written back without it
+#[test]
+fn write_missing_newline() {
+  let test_io = test_io();
+  IOTest{
+    init_buffer: vec![],
+    init_io: test_io,
+    init_clipboard: vec!["dummy"],
+    init_filepath: "",
+    command_input: vec!["e unterminated","2s/b/c/","w","W numbers","w copy"],
+    expected_buffer: vec!["a","c"],
+    expected_buffer_saved: true,
+    expected_selection: (2,2),
+    expected_file_changes: vec![
+      ("unterminated","a\nc"),
+      // Appending writes the newline, to not join lines with what follows
+      ("numbers","4\n5\n2\n1\na\nc\n"),
+      ("copy","a\nc"),
+    ],
+    expected_clipboard: vec!["b"],
+    expected_filepath: "copy",
+  }.run();
+}
+
+// A file read with "\r\n" line endings is written with them, also to other
+// paths, and each buffer keeps the line ending of its own file
+#[test]
+fn write_crlf() {
+  let test_io = test_io();
+  IOTest{
+    init_buffer: vec![],
+    init_io: test_io,
+    init_clipboard: vec!["dummy"],
+    init_filepath: "",
+    command_input: vec!["e crlf","w copy","b+text","w other","b1","W copy"],
+    expected_buffer: vec!["a","b"],
+    expected_buffer_saved: true,
+    expected_selection: (1,2),
+    expected_file_changes: vec![
+      ("copy","a\r\nb\r\na\r\nb\r\n"),
+      ("other","file\ndata\nin\nfile\n"),
+    ],
+    expected_clipboard: vec!["dummy"],
+    expected_filepath: "copy",
+  }.run();
+}
+
+// Refuses to overwrite a file changed since it was read, unless repeated
+#[test]
+fn write_changed_file() {
+  use add_ed::{Ed, EdError, ui::ScriptedUI};
+  let mut io = test_io();
+  let macros = HashMap::new();
+  let mut ui = ScriptedUI{ input: vec![].into(), print_ui: None };
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.run_command(&mut ui, "e text").unwrap();
+  ed.run_command(&mut ui, "1d").unwrap();
+  assert!(!ed.file_changed().unwrap());
+  // Simulate another program changing the file, keeping the editor's state
+  let history = std::mem::take(&mut ed.history);
+  drop(ed);
+  io.fake_fs.insert("text".to_owned(), "changed\n".to_owned());
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.history = history;
+  ed.file = "text".to_owned();
+  assert!(ed.file_changed().unwrap());
+  assert_eq!(
+    ed.run_command(&mut ui, "w"),
+    Err(EdError::FileChanged("text".to_owned())),
+  );
+  // Appending and writing to other files isn't affected
+  ed.run_command(&mut ui, "1W text").unwrap();
+  ed.run_command(&mut ui, "w other").unwrap();
+  ed.file = "text".to_owned();
+  assert!(ed.file_changed().unwrap(), "Appending hid the change.");
+  // Repeating the write directly forces it
+  assert!(ed.run_command(&mut ui, "w").is_err());
+  ed.run_command(&mut ui, "#").unwrap();
+  assert!(ed.run_command(&mut ui, "w").is_err());
+  ed.run_command(&mut ui, "w").unwrap();
+  assert!(!ed.file_changed().unwrap());
+  drop(ed);
+  assert_eq!(&io.fake_fs["text"], "data\nin\nfile\n");
+  assert_eq!(&io.fake_fs["other"], "data\nin\nfile\n");
+}
+
 // Verify that writing to shell commands behaves as intended
 #[test]
 fn write_command() {
@@ -5,10 +5,13 @@
 mod shared;
 use shared::fixtures::{
   BasicTest,
+  ErrorTest,
   PrintTest,
 };
 use shared::mock_ui::Print;
 
+use add_ed::error::EdError;
+
 // Verify behaviour of 'x' command
 //
 // - Takes optional index/selection
@@ -102,3 +105,70 @@ fn paste_before_noselection_print() {
     expected_history_tags: vec!["Xp"],
   }.run()
 }
+
+// Test pasting from a named register
+#[test]
+fn paste_register() {
+  BasicTest{
+    init_buffer: vec!["a","b"],
+    init_clipboard: vec!["c"],
+    command_input: vec!["1y\"a","0x\"a", "$x"],
+    expected_buffer: vec!["a","a","b","c"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec!["c"],
+    expected_selection: (4,4),
+    expected_history_tags: vec!["0x\"a","$x"],
+  }.run()
+}
+
+// Test pasting from a register that is empty
+#[test]
+fn paste_empty_register() {
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["x\"a"],
+    expected_error: EdError::RegisterEmpty('a'),
+  }.run()
+}
+
+// Test pasting older clipboard contents from the clipboard ring
+#[test]
+fn paste_clipboard_ring() {
+  BasicTest{
+    init_buffer: vec!["a","b"],
+    init_clipboard: vec!["c"],
+    command_input: vec!["1y","2d","0x1","$x2"],
+    expected_buffer: vec!["a","a","c"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec!["b"],
+    expected_selection: (3,3),
+    expected_history_tags: vec!["2d","0x1","$x2"],
+  }.run()
+}
+
+// Test listing the clipboard ring, and pasting past its end
+#[test]
+fn list_clipboard_ring() {
+  PrintTest{
+    init_buffer: vec!["a","b"],
+    init_clipboard: vec!["c"],
+    command_input: vec!["1,2y","Y"],
+    expected_buffer: vec!["a","b"],
+    expected_buffer_saved: true,
+    expected_selection: (1,2),
+    expected_clipboard: vec!["a","b"],
+    expected_prints: vec![
+      Print{
+        text: vec!["0: a (2 line(s))\n1: c (1 line(s))".to_string()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec![],
+  }.run();
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["x1"],
+    expected_error: EdError::ClipboardIndexTooBig{index: 1, ring_len: 1},
+  }.run()
+}